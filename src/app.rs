@@ -1,9 +1,17 @@
 use crate::{
     button::Button,
+    config::ConfigStore,
     display::Display,
+    dns::start_captive_dns,
+    console::start_console,
+    http_server::start_http_server,
+    mdns::start_mdns,
     state::State,
-    weather::WeatherClient,
-    wifi::{WiFiManager, WifiStatus},
+    weather::{
+        unix_time, WeatherAlert, WeatherClient, WeatherConfig, WeatherData, WeatherError,
+        WeatherSnapshot,
+    },
+    wifi::{WiFiManager, WifiInfo, WifiStatus},
 };
 use chrono::Utc;
 use chrono_tz::Asia::Shanghai;
@@ -15,35 +23,58 @@ use esp_idf_svc::{
         task::block_on,
         units::KiloHertz,
     },
-    nvs::{EspDefaultNvsPartition, EspNvs},
+    eventloop::EspSystemEventLoop,
+    http::server::EspHttpServer,
+    mdns::EspMdns,
+    nvs::EspDefaultNvsPartition,
     sntp::{EspSntp, SntpConf, SyncStatus},
 };
-use log::info;
+use log::{info, warn};
 use std::{
+    net::Ipv4Addr,
     sync::{
-        mpsc::{self, Sender},
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
         Arc, Mutex,
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+const WIFI_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const WIFI_RETRY_MIN: Duration = Duration::from_secs(2);
+const WIFI_RETRY_MAX: Duration = Duration::from_secs(300);
+const WEATHER_RETRY_MAX: Duration = Duration::from_secs(3600);
+const WEATHER_RETRY_MIN: Duration = Duration::from_secs(30);
+const WEATHER_RATE_LIMIT_WAIT: Duration = Duration::from_secs(900);
+const BUTTON_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const LONG_PRESS: Duration = Duration::from_secs(1);
+
 #[derive(Clone, Debug)]
 pub enum AppPage {
     Home,
+    Forecast,
+    Hourly,
+    AirQuality,
     WifiConfig,
 }
 impl AppPage {
     pub fn next(&self) -> Self {
         match self {
-            AppPage::Home => AppPage::WifiConfig,
+            AppPage::Home => AppPage::Forecast,
+            AppPage::Forecast => AppPage::Hourly,
+            AppPage::Hourly => AppPage::AirQuality,
+            AppPage::AirQuality => AppPage::WifiConfig,
             AppPage::WifiConfig => AppPage::Home,
         }
     }
     pub fn prev(&self) -> Self {
         match self {
             AppPage::Home => AppPage::WifiConfig,
-            AppPage::WifiConfig => AppPage::Home,
+            AppPage::Forecast => AppPage::Home,
+            AppPage::Hourly => AppPage::Forecast,
+            AppPage::AirQuality => AppPage::Hourly,
+            AppPage::WifiConfig => AppPage::AirQuality,
         }
     }
 }
@@ -52,8 +83,12 @@ impl AppPage {
 pub enum DisplayMessage {
     ShowPage(AppPage),
     UpdateTime(String, String),
-    UpdateWeather(String, String),
-    UpdateWifi(WifiStatus, String),
+    UpdateWeather(WeatherData),
+    WeatherUnchanged,
+    WeatherFailed(String),
+    UpdateAlerts(Vec<WeatherAlert>),
+    AcknowledgeAlerts,
+    UpdateWifi(WifiStatus, WifiInfo),
 }
 
 pub struct App {
@@ -61,16 +96,25 @@ pub struct App {
     display_tx: Sender<DisplayMessage>,
     wifi: Arc<Mutex<WiFiManager<'static>>>,
     weather_client: Arc<Mutex<WeatherClient>>,
+    config: Arc<Mutex<ConfigStore>>,
+    // 发送任意消息即可让天气线程立即刷新
+    weather_refresh_tx: Sender<()>,
+    weather_refresh_rx: Option<Receiver<()>>,
     display: Arc<Mutex<Display<'static>>>,
     state: Arc<Mutex<State>>,
     left_button: Arc<Mutex<Button<Gpio4>>>,
     right_button: Arc<Mutex<Button<Gpio15>>>,
+    ap_ip: Ipv4Addr,
+    // STA 未连接时才开启配网用的 DNS 劫持
+    captive_dns: Arc<AtomicBool>,
+    http_server: Option<EspHttpServer<'static>>,
+    mdns: Arc<Mutex<Option<EspMdns>>>,
 }
 
 impl App {
     pub fn new() -> anyhow::Result<Self> {
         let nvs_partition = EspDefaultNvsPartition::take()?;
-        let mut wifi_nvs = EspNvs::new(nvs_partition, "wifi", true)?;
+        let sys_loop = EspSystemEventLoop::take()?;
 
         let peripherals = Peripherals::take().unwrap();
         // config i2c
@@ -83,20 +127,49 @@ impl App {
         let i2c = I2cDriver::new(i2c, sda, scl, &config).unwrap();
 
         let display = Arc::new(Mutex::new(Display::new(i2c)));
-        let wifi = Arc::new(Mutex::new(WiFiManager::new(modem)?));
-        let weather_client = Arc::new(Mutex::new(WeatherClient::new()));
+        let wifi = WiFiManager::new(modem, sys_loop, nvs_partition.clone())?;
+        let ap_ip = wifi.ap_ip()?;
+        let wifi = Arc::new(Mutex::new(wifi));
+        let config_store = ConfigStore::new(nvs_partition)?;
+        let weather_config: WeatherConfig = config_store.load(ConfigStore::WEATHER);
+        let pinned_certs = weather_config
+            .pinned
+            .iter()
+            .filter_map(|provider| Some((*provider, config_store.load_str(provider.cert_key())?)))
+            .collect();
+        let weather_client = WeatherClient::new(weather_config, pinned_certs);
+        let mut state = State::new(weather_client.refresh_interval());
+        let weather_client = Arc::new(Mutex::new(weather_client));
+        // 先显示上一次缓存的天气，联网获取成功后替换
+        let cache: Option<WeatherSnapshot> = config_store.load(ConfigStore::WEATHER_CACHE);
+        if let Some(mut snapshot) = cache {
+            // 之后的 304 说明缓存的数据在那时仍是最新的
+            let checked_at: i64 = config_store.load(ConfigStore::WEATHER_CHECKED);
+            snapshot.fetched_at = snapshot.fetched_at.max(checked_at);
+            info!("Restored cached weather from {}", snapshot.fetched_at);
+            state.restore_weather(snapshot);
+        }
         let (display_tx, display_rx) = mpsc::channel();
+        let (weather_refresh_tx, weather_refresh_rx) = mpsc::channel();
         let ins = Self {
             current_page: Arc::new(Mutex::new(AppPage::Home)),
             wifi,
             weather_client,
+            config: Arc::new(Mutex::new(config_store)),
+            weather_refresh_tx,
+            weather_refresh_rx: Some(weather_refresh_rx),
             display,
             display_tx,
-            state: Arc::new(Mutex::new(State::new())),
+            state: Arc::new(Mutex::new(state)),
             left_button: Arc::new(Mutex::new(Button::new(peripherals.pins.gpio4)?)),
             right_button: Arc::new(Mutex::new(Button::new(peripherals.pins.gpio15)?)),
+            ap_ip,
+            captive_dns: Arc::new(AtomicBool::new(true)),
+            http_server: None,
+            mdns: Arc::new(Mutex::new(None)),
         };
         let ins_state = ins.state.clone();
+        let ins_current_page = ins.current_page.clone();
         thread::Builder::new()
             .stack_size(6000)
             .spawn(move || {
@@ -104,16 +177,29 @@ impl App {
                     info!("DisplayController: {:?}", message);
                     match message {
                         DisplayMessage::ShowPage(page) => {
+                            *ins_current_page.lock().unwrap() = page.clone();
                             ins_state.lock().unwrap().update_page(page);
                         }
                         DisplayMessage::UpdateTime(date, time) => {
                             ins_state.lock().unwrap().update_date_time(date, time);
                         }
-                        DisplayMessage::UpdateWeather(weather, city) => {
-                            ins_state.lock().unwrap().update_weather(weather, city);
+                        DisplayMessage::UpdateWeather(weather) => {
+                            ins_state.lock().unwrap().update_weather(weather);
                         }
-                        DisplayMessage::UpdateWifi(status, ip) => {
-                            ins_state.lock().unwrap().update_wifi(status, ip);
+                        DisplayMessage::WeatherUnchanged => {
+                            ins_state.lock().unwrap().weather_unchanged();
+                        }
+                        DisplayMessage::WeatherFailed(error) => {
+                            ins_state.lock().unwrap().weather_failed(error);
+                        }
+                        DisplayMessage::UpdateAlerts(alerts) => {
+                            ins_state.lock().unwrap().update_alerts(alerts);
+                        }
+                        DisplayMessage::AcknowledgeAlerts => {
+                            ins_state.lock().unwrap().acknowledge_alerts();
+                        }
+                        DisplayMessage::UpdateWifi(status, info) => {
+                            ins_state.lock().unwrap().update_wifi(status, info);
                         }
                     }
                 }
@@ -122,42 +208,160 @@ impl App {
         Ok(ins)
     }
     pub fn run(&mut self) {
+        self.advertise();
         self.wifi_thread();
+        self.portal();
         self.sntp_thread();
         self.weather_thread();
         self.actions_thread();
+        if let Err(e) = start_console(
+            self.weather_client.clone(),
+            self.config.clone(),
+            self.weather_refresh_tx.clone(),
+        ) {
+            warn!("Serial console failed to start: {:?}", e);
+        }
 
+        // 有未确认的预警时占用整个屏幕，横幅每秒闪烁一次
+        let mut blink = false;
         loop {
-            let page = self.current_page.lock().unwrap().clone();
-            self.show_page(page);
+            blink = !blink;
+            let alert = self.state.lock().unwrap().pending_alert().cloned();
+            match alert {
+                Some(alert) => self.display.lock().unwrap().show_alert(&alert, blink),
+                None => {
+                    let page = self.current_page.lock().unwrap().clone();
+                    self.show_page(page);
+                }
+            }
             thread::sleep(Duration::from_secs(1));
         }
     }
     fn wifi_thread(&mut self) {
         let wifi = self.wifi.clone();
         let display_tx = self.display_tx.clone();
+        let captive_dns = self.captive_dns.clone();
         thread::Builder::new()
             .stack_size(9000)
             .spawn(move || {
+                // 断线事件只用来唤醒下面的循环，真正的状态以 get_wifi_status 为准
+                let (event_tx, event_rx) = mpsc::channel();
+                let _subscription = wifi
+                    .lock()
+                    .unwrap()
+                    .on_disconnect(move || {
+                        let _ = event_tx.send(());
+                    })
+                    .unwrap();
+
                 block_on(async {
-                    wifi.lock().unwrap().connect().await.unwrap();
-                    loop {
-                        let (status, ip) = wifi.lock().unwrap().get_wifi_status().await.unwrap();
+                    display_tx
+                        .send(DisplayMessage::UpdateWifi(
+                            WifiStatus::Connecting,
+                            WifiInfo::default(),
+                        ))
+                        .unwrap();
+                    let connected = match wifi.lock().unwrap().connect().await {
+                        Ok(()) => true,
+                        Err(e) => {
+                            warn!("Wi-Fi connect failed: {:?}", e);
+                            false
+                        }
+                    };
+                    if !connected || !wifi.lock().unwrap().has_credentials() {
+                        info!("Wi-Fi not configured, showing config page.");
+                        display_tx
+                            .send(DisplayMessage::ShowPage(AppPage::WifiConfig))
+                            .unwrap();
+                    }
 
-                        if status == WifiStatus::Connected {
-                            info!("Wi-Fi connected! Breaking the loop.");
+                    let mut last_status = (WifiStatus::Connecting, WifiInfo::default());
+                    let mut backoff = WIFI_RETRY_MIN;
+                    loop {
+                        let (status, info) = match wifi.lock().unwrap().get_wifi_status().await {
+                            Ok(status) => status,
+                            Err(e) => {
+                                warn!("Wi-Fi status failed: {:?}", e);
+                                (WifiStatus::Disconnected, WifiInfo::default())
+                            }
+                        };
+                        if status != last_status.0 || info != last_status.1 {
+                            info!("Wi-Fi status: {}", status.to_string());
                             display_tx
-                                .send(DisplayMessage::UpdateWifi(status, ip))
+                                .send(DisplayMessage::UpdateWifi(status.clone(), info.clone()))
                                 .unwrap();
-                            break;
+                            last_status = (status.clone(), info);
+                            captive_dns.store(status != WifiStatus::Connected, Ordering::Relaxed);
+                        }
+
+                        if status == WifiStatus::Connected {
+                            backoff = WIFI_RETRY_MIN;
+                            // 断线事件或定时检查
+                            let _ = event_rx.recv_timeout(WIFI_CHECK_INTERVAL);
+                            continue;
+                        }
+
+                        if !wifi.lock().unwrap().has_credentials() {
+                            let _ = event_rx.recv_timeout(WIFI_CHECK_INTERVAL);
+                            continue;
+                        }
+
+                        info!("Wi-Fi not connected. Reconnecting...");
+                        display_tx
+                            .send(DisplayMessage::UpdateWifi(
+                                WifiStatus::Connecting,
+                                WifiInfo::default(),
+                            ))
+                            .unwrap();
+                        last_status = (WifiStatus::Connecting, WifiInfo::default());
+                        // 先释放锁再退避，等待期间网页仍可扫描和修改配置
+                        let result = wifi.lock().unwrap().reconnect();
+                        if let Err(e) = result {
+                            warn!(
+                                "Wi-Fi reconnect failed: {:?}, retrying in {} seconds",
+                                e,
+                                backoff.as_secs()
+                            );
+                            thread::sleep(backoff);
+                            backoff = (backoff * 2).min(WIFI_RETRY_MAX);
                         }
-                        info!("Wi-Fi not connected. Retrying in 2 seconds...");
-                        thread::sleep(Duration::from_secs(2));
+                        // 重连过程中产生的断线事件已经过时
+                        while event_rx.try_recv().is_ok() {}
                     }
                 });
             })
             .unwrap();
     }
+    /// 配网门户：HTTP 表单 + DNS 劫持，通过 SoftAP 访问
+    fn portal(&mut self) {
+        if let Err(e) = start_captive_dns(self.ap_ip, self.captive_dns.clone()) {
+            warn!("Captive DNS failed to start: {:?}", e);
+        }
+        match start_http_server(
+            self.wifi.clone(),
+            self.weather_client.clone(),
+            self.config.clone(),
+            self.weather_refresh_tx.clone(),
+            self.state.clone(),
+            self.display_tx.clone(),
+            self.mdns.clone(),
+        ) {
+            Ok(server) => self.http_server = Some(server),
+            Err(e) => warn!("HTTP server failed to start: {:?}", e),
+        }
+    }
+    /// mDNS 会跟随网卡的连接状态自动发布，启动一次即可；修改主机名时由 HTTP 接口重新广播
+    fn advertise(&mut self) {
+        let (hostname, device_id) = {
+            let wifi = self.wifi.lock().unwrap();
+            (wifi.hostname().to_string(), wifi.device_id())
+        };
+        let result = device_id.and_then(|device_id| start_mdns(&hostname, &device_id));
+        match result {
+            Ok(mdns) => *self.mdns.lock().unwrap() = Some(mdns),
+            Err(e) => warn!("mDNS failed to start: {:?}", e),
+        }
+    }
     fn sntp_thread(&mut self) {
         let display_tx = self.display_tx.clone();
         let state = self.state.clone();
@@ -201,11 +405,14 @@ impl App {
     }
     fn weather_thread(&mut self) {
         let weather_client = self.weather_client.clone();
+        let config = self.config.clone();
         let display_tx = self.display_tx.clone();
         let state = self.state.clone();
+        let refresh_rx = self.weather_refresh_rx.take().unwrap();
 
         thread::Builder::new()
-            .stack_size(9000)
+            // https 请求的 TLS 握手在这个线程中进行，需要更大的栈
+            .stack_size(12288)
             .spawn(move || {
                 block_on(async {
                     loop {
@@ -217,15 +424,79 @@ impl App {
                         thread::sleep(Duration::from_secs(1));
                     }
 
+                    let mut backoff = WEATHER_RETRY_MIN;
+                    // 被限流期间即使收到刷新请求也不访问接口
+                    let mut rate_limited_until: Option<Instant> = None;
                     loop {
-                        let weather_data = weather_client.lock().unwrap().fetch_weather().unwrap();
-                        display_tx
-                            .send(DisplayMessage::UpdateWeather(
-                                weather_data.temp,
-                                weather_data.city,
-                            ))
-                            .unwrap();
-                        thread::sleep(Duration::from_secs(3600));
+                        let remaining = rate_limited_until
+                            .and_then(|until| until.checked_duration_since(Instant::now()));
+                        if let Some(remaining) = remaining {
+                            info!("天气模块: 限流中，{} 秒后再请求", remaining.as_secs());
+                            let _ = refresh_rx.recv_timeout(remaining);
+                            continue;
+                        }
+                        rate_limited_until = None;
+
+                        let (result, alerts, interval) = {
+                            let mut client = weather_client.lock().unwrap();
+                            let result = client.fetch_weather();
+                            // 天气都获取失败时就不再请求预警了
+                            let alerts = result.is_ok().then(|| client.fetch_alerts());
+                            (result, alerts, client.refresh_interval())
+                        };
+                        // 刷新间隔可能在网页上改过，过期的判断跟着变
+                        state.lock().unwrap().set_refresh_interval(interval);
+                        match alerts {
+                            Some(Ok(alerts)) => {
+                                display_tx.send(DisplayMessage::UpdateAlerts(alerts)).unwrap();
+                            }
+                            Some(Err(e)) => warn!("天气模块: 获取预警失败: {}", e),
+                            None => {}
+                        }
+                        let wait = match result {
+                            Ok(Some(weather_data)) => {
+                                backoff = WEATHER_RETRY_MIN;
+                                save_weather_cache(&config, &weather_data);
+                                display_tx
+                                    .send(DisplayMessage::UpdateWeather(weather_data))
+                                    .unwrap();
+                                interval
+                            }
+                            Ok(None) => {
+                                backoff = WEATHER_RETRY_MIN;
+                                // 记下数据仍是最新的时间，否则重启后缓存会被当成过期数据
+                                save_weather_checked(&config);
+                                display_tx.send(DisplayMessage::WeatherUnchanged).unwrap();
+                                interval
+                            }
+                            Err(e) => {
+                                // 失败时保留上一次的数据，按指数退避重试
+                                let wait = match e {
+                                    // 服务器给出了 Retry-After 时以它为准
+                                    WeatherError::RateLimited(Some(retry_after)) => {
+                                        retry_after.max(WEATHER_RETRY_MIN)
+                                    }
+                                    WeatherError::RateLimited(None) => {
+                                        backoff.max(WEATHER_RATE_LIMIT_WAIT)
+                                    }
+                                    _ => backoff,
+                                };
+                                if let WeatherError::RateLimited(_) = e {
+                                    rate_limited_until = Some(Instant::now() + wait);
+                                }
+                                warn!("天气模块: 获取失败: {}，{} 秒后重试", e, wait.as_secs());
+                                display_tx
+                                    .send(DisplayMessage::WeatherFailed(e.to_string()))
+                                    .unwrap();
+                                backoff = (wait * 2).min(WEATHER_RETRY_MAX);
+                                wait
+                            }
+                        };
+                        // 配置变化、网页或长按按钮时会被提前唤醒
+                        if refresh_rx.recv_timeout(wait).is_ok() {
+                            info!("天气模块: 立即刷新");
+                            while refresh_rx.try_recv().is_ok() {}
+                        }
                     }
                 })
             })
@@ -234,40 +505,65 @@ impl App {
 
     fn actions_thread(&mut self) {
         let display_tx = self.display_tx.clone();
+        let weather_refresh_tx = self.weather_refresh_tx.clone();
         let current_page = self.current_page.clone();
+        let state = self.state.clone();
         let left = self.left_button.clone();
         let right = self.right_button.clone();
 
         thread::Builder::new()
             .stack_size(9000)
             .spawn(move || {
-                let display_tx_clone = display_tx.clone();
-                let current_page_clone = current_page.clone();
+                // 中断里只记录按下，确认预警和翻页都在下面的轮询中处理
+                let left_clicked = Arc::new(AtomicBool::new(false));
+                let right_clicked = Arc::new(AtomicBool::new(false));
+                let clicked = left_clicked.clone();
                 left.lock()
                     .unwrap()
-                    .subscribe(move || {
-                        display_tx_clone
-                            .send(DisplayMessage::ShowPage(
-                                current_page_clone.lock().unwrap().prev(),
-                            ))
-                            .unwrap();
-                    })
+                    .subscribe(move || clicked.store(true, Ordering::Relaxed))
                     .unwrap();
-                let display_tx_clone = display_tx.clone();
-                let current_page_clone = current_page.clone();
+                let clicked = right_clicked.clone();
                 right
                     .lock()
                     .unwrap()
-                    .subscribe(move || {
-                        display_tx_clone
-                            .send(DisplayMessage::ShowPage(
-                                current_page_clone.lock().unwrap().next(),
-                            ))
-                            .unwrap();
-                    })
+                    .subscribe(move || clicked.store(true, Ordering::Relaxed))
                     .unwrap();
+                let on_click = |turn: fn(&AppPage) -> AppPage| {
+                    if acknowledge_alert(&state, &display_tx) {
+                        return;
+                    }
+                    let page = turn(&current_page.lock().unwrap());
+                    display_tx.send(DisplayMessage::ShowPage(page)).unwrap();
+                };
+                // 短按在松开时才翻页，长按任意按钮立即刷新天气且不翻页
+                let mut pressed_for = Duration::ZERO;
+                let mut pending: Option<fn(&AppPage) -> AppPage> = None;
                 loop {
-                    thread::sleep(Duration::from_secs(1));
+                    if left_clicked.swap(false, Ordering::Relaxed) {
+                        pending = Some(AppPage::prev);
+                    }
+                    if right_clicked.swap(false, Ordering::Relaxed) {
+                        pending = Some(AppPage::next);
+                    }
+                    let pressed =
+                        left.lock().unwrap().is_pressed() || right.lock().unwrap().is_pressed();
+                    if !pressed {
+                        let clicked = pending.take().map(on_click).is_some();
+                        if clicked || pressed_for > Duration::ZERO {
+                            // 松开后再打开中断，忽略按下和松开时的抖动
+                            let _ = left.lock().unwrap().enable_interrupt();
+                            let _ = right.lock().unwrap().enable_interrupt();
+                        }
+                        pressed_for = Duration::ZERO;
+                    } else if pressed_for < LONG_PRESS {
+                        pressed_for += BUTTON_POLL_INTERVAL;
+                        if pressed_for >= LONG_PRESS {
+                            info!("长按按钮，立即刷新天气");
+                            pending = None;
+                            let _ = weather_refresh_tx.send(());
+                        }
+                    }
+                    thread::sleep(BUTTON_POLL_INTERVAL);
                 }
             })
             .unwrap();
@@ -276,6 +572,9 @@ impl App {
     fn show_page(&mut self, page: AppPage) {
         match page {
             AppPage::Home => self.render_home_page(),
+            AppPage::Forecast => self.render_forecast_page(),
+            AppPage::Hourly => self.render_hourly_page(),
+            AppPage::AirQuality => self.render_air_quality_page(),
             AppPage::WifiConfig => self.render_wifi_page(),
         }
     }
@@ -284,7 +583,61 @@ impl App {
         let state = self.state.lock().unwrap().clone();
         self.display.lock().unwrap().update_home(state);
     }
+    fn render_forecast_page(&mut self) {
+        let state = self.state.lock().unwrap().clone();
+        self.display.lock().unwrap().show_forecast(state);
+    }
+    fn render_hourly_page(&mut self) {
+        let state = self.state.lock().unwrap().clone();
+        self.display.lock().unwrap().show_hourly(state);
+    }
+    fn render_air_quality_page(&mut self) {
+        let state = self.state.lock().unwrap().clone();
+        self.display.lock().unwrap().show_air_quality(state);
+    }
     fn render_wifi_page(&mut self) {
-        self.display.lock().unwrap().show_wifi_config();
+        let ap_ip = self.ap_ip.to_string();
+        self.display
+            .lock()
+            .unwrap()
+            .show_wifi_config(WiFiManager::AP_SSID, &ap_ip);
+    }
+}
+
+/// 时钟还没有同步时不缓存，等下一次刷新
+fn save_weather_cache(config: &Mutex<ConfigStore>, weather: &WeatherData) {
+    let Some(snapshot) = WeatherSnapshot::new(weather) else {
+        info!("天气模块: 时钟未同步，暂不缓存");
+        return;
+    };
+    if let Err(e) = config
+        .lock()
+        .unwrap()
+        .save(ConfigStore::WEATHER_CACHE, &snapshot)
+    {
+        warn!("天气模块: 缓存失败: {:?}", e);
+    }
+}
+
+/// 只写一个时间戳，避免每次 304 都重写几 KB 的缓存磨损 flash
+fn save_weather_checked(config: &Mutex<ConfigStore>) {
+    let Some(now) = unix_time() else {
+        return;
+    };
+    if let Err(e) = config
+        .lock()
+        .unwrap()
+        .save(ConfigStore::WEATHER_CHECKED, &now)
+    {
+        warn!("天气模块: 保存检查时间失败: {:?}", e);
+    }
+}
+
+/// 预警占用屏幕时，按键只用来确认预警而不翻页
+fn acknowledge_alert(state: &Mutex<State>, display_tx: &Sender<DisplayMessage>) -> bool {
+    if state.lock().unwrap().pending_alert().is_none() {
+        return false;
     }
+    display_tx.send(DisplayMessage::AcknowledgeAlerts).unwrap();
+    true
 }
@@ -1,5 +1,3 @@
-use std::sync::{Arc, Mutex};
-
 use esp_idf_svc::hal::{
     gpio::{Input, InputPin, InterruptType, OutputPin, PinDriver, Pull},
     peripheral::Peripheral,
@@ -9,7 +7,6 @@ use log::info;
 
 pub struct Button<P: InputPin> {
     btn: PinDriver<'static, P, Input>,
-    callback: Option<Arc<Mutex<Box<dyn FnMut() + Send>>>>,
 }
 impl<P> Button<P>
 where
@@ -20,24 +17,24 @@ where
         btn.set_pull(Pull::Up)?;
         btn.set_interrupt_type(InterruptType::NegEdge)?;
 
-        Ok(Self {
-            btn,
-            callback: None,
-        })
+        Ok(Self { btn })
+    }
+    /// 按钮接地有效
+    pub fn is_pressed(&self) -> bool {
+        self.btn.is_low()
     }
+    /// 回调在中断上下文中执行，只能设置标志之类的通知，不能加锁、打印日志或发送消息
     pub fn subscribe<F: FnMut() + Send + 'static>(&mut self, callback: F) -> anyhow::Result<()> {
-        let callback = Arc::new(Mutex::new(Box::new(callback) as Box<dyn FnMut() + Send>));
-        let callback_clone = callback.clone();
         unsafe {
-            self.btn.subscribe(move || {
-                log::info!("🔥 按钮中断触发！");
-                if let Ok(mut cb) = callback_clone.lock() {
-                    (*cb)();
-                }
-            })?;
+            self.btn.subscribe(callback)?;
         }
+        self.btn.enable_interrupt()?;
         log::info!("✅ 按钮订阅成功！");
-        self.callback = Some(callback);
+        Ok(())
+    }
+    /// 中断触发一次后会被自动关闭，处理完按键后重新打开
+    pub fn enable_interrupt(&mut self) -> anyhow::Result<()> {
+        self.btn.enable_interrupt()?;
         Ok(())
     }
 }
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// 应用配置，以 JSON 形式保存在 "config" NVS 命名空间中
+pub struct ConfigStore {
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl ConfigStore {
+    pub const WEATHER: &'static str = "weather";
+    pub const WEATHER_CACHE: &'static str = "weather_cache";
+    /// 服务器返回 304 时只更新这个时间戳，不重写整个缓存
+    pub const WEATHER_CHECKED: &'static str = "weather_checked";
+
+    pub fn new(nvs_partition: EspDefaultNvsPartition) -> anyhow::Result<Self> {
+        Ok(Self {
+            nvs: EspNvs::new(nvs_partition, "config", true)?,
+        })
+    }
+    /// 读取配置，不存在或格式不对时返回默认值
+    pub fn load<T: DeserializeOwned + Default>(&self, key: &str) -> T {
+        match self.load_json(key) {
+            Ok(Some(value)) => value,
+            Ok(None) => T::default(),
+            Err(e) => {
+                log::warn!("Config {} is invalid, using defaults: {:?}", key, e);
+                T::default()
+            }
+        }
+    }
+    fn load_json<T: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Option<T>> {
+        let Some(len) = self.nvs.str_len(key)? else {
+            return Ok(None);
+        };
+        let mut buf = vec![0u8; len];
+        match self.nvs.get_str(key, &mut buf)? {
+            Some(json) => Ok(Some(serde_json::from_str(json)?)),
+            None => Ok(None),
+        }
+    }
+    pub fn save<T: Serialize>(&mut self, key: &str, value: &T) -> anyhow::Result<()> {
+        let json = serde_json::to_string(value)?;
+        self.nvs.set_str(key, &json)?;
+        Ok(())
+    }
+    /// 单独保存的大段文本（例如证书），不占用 JSON 配置 4000 字节的长度；
+    /// 读取失败时当作不存在
+    pub fn load_str(&self, key: &str) -> Option<String> {
+        let result = self.nvs.str_len(key).and_then(|len| match len {
+            Some(len) => {
+                let mut buf = vec![0u8; len];
+                Ok(self.nvs.get_str(key, &mut buf)?.map(str::to_string))
+            }
+            None => Ok(None),
+        });
+        result.unwrap_or_else(|e| {
+            log::warn!("Config {} is unreadable: {:?}", key, e);
+            None
+        })
+    }
+    pub fn save_str(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        self.nvs.set_str(key, value)?;
+        Ok(())
+    }
+    pub fn remove(&mut self, key: &str) -> anyhow::Result<()> {
+        self.nvs.remove(key)?;
+        Ok(())
+    }
+}
+
+/// 解析 `application/x-www-form-urlencoded` 表单
+pub fn parse_form(body: &str) -> HashMap<String, String> {
+    body.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (url_decode(key), url_decode(value))
+        })
+        .collect()
+}
+
+/// 从配网表单中取出 Wi-Fi 名称和密码
+pub fn parse_form_data(body: &str) -> Option<(String, String)> {
+    let mut form = parse_form(body);
+    let ssid = form.remove("ssid").filter(|ssid| !ssid.is_empty())?;
+    let password = form.remove("password").unwrap_or_default();
+    Some((ssid, password))
+}
+
+pub fn url_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => match (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                (Some(hi), Some(lo)) => {
+                    out.push(hi << 4 | lo);
+                    i += 2;
+                }
+                _ => out.push(b'%'),
+            },
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// 对 URL 查询参数进行百分号编码
+pub fn url_encode(input: &str) -> String {
+    let mut out = String::with_capacity(input.len() * 3);
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+fn hex(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|d| d as u8)
+}
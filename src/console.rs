@@ -0,0 +1,106 @@
+use std::{
+    io::{self, BufRead},
+    sync::{mpsc::Sender, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use log::{info, warn};
+
+use crate::{config::ConfigStore, weather::WeatherClient};
+
+const HELP: &str = "commands:
+  location                     show the current location
+  city <code> [name]           set the sojson city code
+  coords <lat> <lon> [name]    set the coordinates for Open-Meteo / OpenWeatherMap";
+
+/// 串口命令行，用于在没有网页的情况下修改天气位置
+pub fn start_console(
+    weather_client: Arc<Mutex<WeatherClient>>,
+    config: Arc<Mutex<ConfigStore>>,
+    weather_refresh_tx: Sender<()>,
+) -> anyhow::Result<()> {
+    thread::Builder::new()
+        .stack_size(6000)
+        .spawn(move || {
+            let stdin = io::stdin();
+            let mut line = String::new();
+            loop {
+                // UART 上的 stdin 是非阻塞的，没有数据时稍等再读
+                match stdin.lock().read_line(&mut line) {
+                    Ok(_) if line.ends_with('\n') => {}
+                    _ => {
+                        thread::sleep(Duration::from_millis(100));
+                        continue;
+                    }
+                }
+                let command = line.trim().to_string();
+                line.clear();
+                if command.is_empty() {
+                    continue;
+                }
+                match handle_command(&command, &weather_client, &config) {
+                    Ok(true) => {
+                        let _ = weather_refresh_tx.send(());
+                    }
+                    Ok(false) => {}
+                    Err(e) => warn!("{}: {}", command, e),
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// 返回配置是否被修改
+fn handle_command(
+    command: &str,
+    weather_client: &Arc<Mutex<WeatherClient>>,
+    config: &Arc<Mutex<ConfigStore>>,
+) -> anyhow::Result<bool> {
+    let mut args = command.split_whitespace();
+    let mut weather_config = weather_client.lock().unwrap().config().clone();
+    let location = &mut weather_config.location;
+    match args.next() {
+        Some("location") => {
+            info!("location: {:?}", location);
+            return Ok(false);
+        }
+        Some("city") => {
+            let code = args.next().ok_or_else(|| anyhow::anyhow!("missing city code"))?;
+            if !code.chars().all(|c| c.is_ascii_digit()) {
+                anyhow::bail!("invalid city code: {}", code);
+            }
+            location.code = code.to_string();
+        }
+        Some("coords") => {
+            let latitude: f32 = args
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("missing latitude"))?
+                .parse()?;
+            let longitude: f32 = args
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("missing longitude"))?
+                .parse()?;
+            if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+                anyhow::bail!("coordinates out of range");
+            }
+            location.latitude = latitude;
+            location.longitude = longitude;
+        }
+        _ => {
+            info!("{}", HELP);
+            return Ok(false);
+        }
+    }
+    let name: Vec<&str> = args.collect();
+    if !name.is_empty() {
+        location.name = name.join(" ");
+    }
+    info!("location updated: {:?}", location);
+    config
+        .lock()
+        .unwrap()
+        .save(ConfigStore::WEATHER, &weather_config)?;
+    weather_client.lock().unwrap().set_config(weather_config);
+    Ok(true)
+}
@@ -1,18 +1,24 @@
 use embedded_graphics::{
     draw_target::DrawTarget,
-    image::{Image, ImageRaw},
+    image::{Image, ImageDrawableExt, ImageRaw, SubImage},
     pixelcolor::{raw::LittleEndian, BinaryColor},
-    prelude::{Point, Primitive},
-    primitives::{Line, PrimitiveStyle},
-    text::Text,
+    prelude::{Point, Primitive, Size},
+    primitives::{Line, PrimitiveStyle, Polyline, Rectangle, Triangle},
+    Pixel,
+    text::{Baseline, Text},
     Drawable,
 };
 use embedded_layout::{
-    align::vertical::{self},
-    layout::linear::{spacing::DistributeFill, LinearLayout},
-    prelude::Chain,
+    align::{horizontal, vertical},
+    layout::linear::{
+        spacing::{DistributeFill, FixedMargin},
+        LinearLayout,
+    },
+    prelude::{Chain, Views},
+    View,
 };
 use esp_idf_svc::hal::i2c::I2cDriver;
+use qrcodegen::{QrCode, QrCodeEcc};
 use ssd1306::{
     mode::{BufferedGraphicsMode, DisplayConfig},
     prelude::{DisplayRotation, I2CInterface},
@@ -24,7 +30,116 @@ use u8g2_fonts::{
     U8g2TextStyle,
 };
 
-use crate::{state::State, wifi::WifiStatus};
+use std::time::Duration;
+
+use crate::{
+    state::State,
+    weather::{AirQuality, WeatherAlert, WeatherIcon},
+    wifi::{WifiInfo, WifiStatus},
+};
+
+// 16x16 状态栏图标，生成方式见 assets/img2raw.txt
+const WIFI_SIGNAL_ICONS: [&[u8]; 5] = [
+    include_bytes!("./assets/wifi_0.raw"),
+    include_bytes!("./assets/wifi_1.raw"),
+    include_bytes!("./assets/wifi_2.raw"),
+    include_bytes!("./assets/wifi_3.raw"),
+    include_bytes!("./assets/wifi_4.raw"),
+];
+const WIFI_OFF_ICON: &[u8] = include_bytes!("./assets/wifi_off.raw");
+const WIFI_AP_ICON: &[u8] = include_bytes!("./assets/wifi_ap.raw");
+
+// 天气图标图集，10 个 16x16 图标排成一行，顺序与 WeatherIcon 一致
+const WEATHER_ATLAS: &[u8] = include_bytes!("./assets/weather.raw");
+const WEATHER_ATLAS_WIDTH: u32 = 160;
+
+/// 预报页显示的天数，每天占 32 像素宽
+const FORECAST_DAYS: usize = 4;
+
+/// 逐小时图表的区域：左边留给最高/最低温标签，底部留给时间标签
+const CHART_LEFT: i32 = 28;
+const CHART_WIDTH: i32 = 100;
+/// 上半部分画温度折线
+const TEMPERATURE_TOP: i32 = 2;
+const TEMPERATURE_HEIGHT: i32 = 28;
+/// 下半部分画降水概率柱
+const PRECIPITATION_TOP: i32 = 34;
+const PRECIPITATION_HEIGHT: i32 = 16;
+
+/// 空气质量分级条，每一级一段，用填充图案区分
+const AQI_BAR_TOP: i32 = 46;
+const AQI_BAR_HEIGHT: i32 = 9;
+
+/// 第 level 级的填充图案，级别越高越密
+fn aqi_pattern(level: usize, x: i32, y: i32) -> bool {
+    match level {
+        0 => false,
+        1 => x % 4 == 0 && y % 4 == 0,
+        2 => (x + y) % 4 == 0,
+        3 => (x + y) % 2 == 0,
+        4 => x % 4 != 0 || y % 2 == 0,
+        _ => true,
+    }
+}
+
+/// 例如 "5分钟"、"3小时"、"2天"
+fn format_age(age: Duration) -> String {
+    let minutes = age.as_secs() / 60;
+    match minutes {
+        0..=59 => format!("{}分钟", minutes),
+        60..=1439 => format!("{}小时", minutes / 60),
+        _ => format!("{}天", minutes / 1440),
+    }
+}
+
+/// 从图集中取出一个图标
+fn weather_icon<'a>(
+    atlas: &'a ImageRaw<'static, BinaryColor, LittleEndian>,
+    icon: WeatherIcon,
+) -> SubImage<'a, ImageRaw<'static, BinaryColor, LittleEndian>> {
+    atlas.sub_image(&Rectangle::new(
+        Point::new(icon.index() as i32 * 16, 0),
+        Size::new(16, 16),
+    ))
+}
+
+/// 用今天的日出日落时间判断，没有数据时按 6 点到 18 点算白天
+fn is_night(state: &State) -> bool {
+    // state.time 是 HH:MM:SS，日出日落是 HH:MM，可以直接比较字符串
+    let now = state.time.get(..5).unwrap_or_default();
+    let today = state.weather.as_ref().and_then(|w| w.forecast.first());
+    match today.filter(|t| !t.sunrise.is_empty() && !t.sunset.is_empty()) {
+        Some(today) => now < today.sunrise.as_str() || now >= today.sunset.as_str(),
+        None => !("06:00".."18:00").contains(&now),
+    }
+}
+
+/// 按字符截断，屏幕一行放不下时用
+fn truncate_chars(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// "星期三" 显示为 "周三"
+fn short_weekday(weekday: &str) -> String {
+    match weekday.strip_prefix("星期") {
+        Some(day) => format!("周{}", day),
+        None => weekday.to_string(),
+    }
+}
+
+fn wifi_icon(status: &WifiStatus, info: &WifiInfo) -> &'static [u8] {
+    match status {
+        WifiStatus::Connected => WIFI_SIGNAL_ICONS[info.signal_bars() as usize],
+        WifiStatus::Connecting => WIFI_SIGNAL_ICONS[0],
+        WifiStatus::Provisioning => WIFI_AP_ICON,
+        _ => WIFI_OFF_ICON,
+    }
+}
 
 pub type SSD1306Display<'a> = Ssd1306<
     I2CInterface<I2cDriver<'a>>,
@@ -36,7 +151,8 @@ pub type SSD1306Display<'a> = Ssd1306<
  * |wifi_icon               date|
  * |----------------------------|
  * |  time                      |
- * |  weather                   |
+ * |  icon weather              |
+ * |  humidity / air quality    |
  *  ----------------------------
  *
  *
@@ -72,15 +188,59 @@ impl<'a> Display<'a> {
         // let ip_string = format!("IP: {}", self.wifi_ip);
         // let ip_address = Text::new(&ip_string, Point::new(0, 90), style.clone());
 
-        let wether_string = format!("{} {}", state.city, state.weather);
-        let weather = Text::new(&wether_string, Point::zero(), style_12.clone());
+        let wether_string = match &state.weather {
+            Some(weather) => format!(
+                "{} {:.0}℃ {}",
+                weather.city, weather.temperature, weather.description
+            ),
+            None => String::from("--"),
+        };
+        let atlas = ImageRaw::new(WEATHER_ATLAS, WEATHER_ATLAS_WIDTH);
+        let condition = match &state.weather {
+            Some(weather) if is_night(&state) => weather.icon.at_night(),
+            Some(weather) => weather.icon,
+            None => WeatherIcon::Unknown,
+        };
+        let condition = weather_icon(&atlas, condition);
+        let weather = LinearLayout::horizontal(
+            Chain::new(Image::new(&condition, Point::zero())).append(Text::new(
+                &wether_string,
+                Point::zero(),
+                style_12.clone(),
+            )),
+        )
+        .with_spacing(FixedMargin(2))
+        .with_alignment(vertical::Center)
+        .arrange();
+
+        // 湿度和空气质量，没有数据时留空；数据过期时显示上次更新的时间
+        let mut detail_string = String::new();
+        if state.is_weather_stale() {
+            match state.weather_age() {
+                Some(age) => detail_string.push_str(&format!("数据过期 {}前", format_age(age))),
+                None => detail_string.push_str("数据已过期"),
+            }
+        } else if let Some(weather) = &state.weather {
+            if let Some(humidity) = weather.humidity {
+                detail_string.push_str(&format!("湿度{}% ", humidity));
+            }
+            if let Some(air) = weather.air_quality.as_ref().filter(|a| !a.category.is_empty()) {
+                detail_string.push_str(&format!("空气{}", air.category));
+            }
+        }
+        let detail = Text::new(&detail_string, Point::zero(), style_12.clone());
 
         let time = format!("{}", state.time);
         let time = Text::new(&time, Point::zero(), style_16.clone());
 
-        let date = Text::new(&state.date, Point::zero(), style_12.clone());
+        // 未联网时用状态说明代替日期
+        let status_text = match state.wifi_status {
+            WifiStatus::Connected => state.date.as_str(),
+            ref status => status.label(),
+        };
+        let date = Text::new(status_text, Point::zero(), style_12.clone());
         let raw: ImageRaw<'a, BinaryColor, LittleEndian> =
-            ImageRaw::new(include_bytes!("./assets/wifi.raw"), 16);
+            ImageRaw::new(wifi_icon(&state.wifi_status, &state.wifi_info), 16);
         let icon = Image::new(&raw, Point::zero());
         let bar = LinearLayout::horizontal(Chain::new(icon).append(date))
             .with_spacing(DistributeFill(128))
@@ -90,7 +250,10 @@ impl<'a> Display<'a> {
             .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1)); // 添加样式
 
         let status_bar = LinearLayout::vertical(Chain::new(bar).append(line)).arrange();
-        let views = Chain::new(status_bar).append(time).append(weather);
+        let views = Chain::new(status_bar)
+            .append(time)
+            .append(weather)
+            .append(detail);
 
         let layout = LinearLayout::vertical(views)
             .with_spacing(DistributeFill(64))
@@ -103,7 +266,308 @@ impl<'a> Display<'a> {
         self.screen.flush().unwrap();
     }
 
-    pub fn show_wifi_config(&mut self) {
-        self.show_message("请输入WiFi名称和密码");
+    /// 从今天开始的几天预报，每列依次是星期、天气图标、最高温和最低温
+    pub fn show_forecast(&mut self, state: State) {
+        let style = U8g2TextStyle::new(u8g2_font_wqy12_t_gb2312, BinaryColor::On);
+        self.screen.clear(BinaryColor::Off).unwrap();
+
+        let days = state
+            .weather
+            .as_ref()
+            .map(|weather| &weather.forecast[..weather.forecast.len().min(FORECAST_DAYS)])
+            .unwrap_or_default();
+        if days.is_empty() {
+            Text::new("暂无预报", Point::new(40, 26), style.clone())
+                .draw(&mut self.screen)
+                .unwrap();
+            self.screen.flush().unwrap();
+            return;
+        }
+
+        let labels: Vec<(String, String, String)> = days
+            .iter()
+            .enumerate()
+            .map(|(i, day)| {
+                let weekday = if i == 0 {
+                    String::from("今天")
+                } else {
+                    short_weekday(&day.weekday)
+                };
+                (weekday, format!("{:.0}°", day.high), format!("{:.0}°", day.low))
+            })
+            .collect();
+        let atlas = ImageRaw::new(WEATHER_ATLAS, WEATHER_ATLAS_WIDTH);
+        let icons: Vec<_> = days.iter().map(|day| weather_icon(&atlas, day.icon)).collect();
+        let mut columns: Vec<_> = labels
+            .iter()
+            .zip(icons.iter())
+            .map(|((weekday, high, low), icon)| {
+                let cells = Chain::new(Text::new(weekday, Point::zero(), style.clone()))
+                    .append(Image::new(icon, Point::zero()))
+                    .append(Text::new(high, Point::zero(), style.clone()))
+                    .append(Text::new(low, Point::zero(), style.clone()));
+                LinearLayout::vertical(cells)
+                    .with_alignment(horizontal::Center)
+                    .with_spacing(DistributeFill(64))
+                    .arrange()
+            })
+            .collect();
+        LinearLayout::horizontal(Views::new(&mut columns))
+            .with_spacing(DistributeFill(128))
+            .arrange()
+            .draw(&mut self.screen)
+            .unwrap();
+
+        self.screen.flush().unwrap();
+    }
+
+    /// 未来 24 小时的温度折线和降水概率柱状图
+    pub fn show_hourly(&mut self, state: State) {
+        let style = U8g2TextStyle::new(u8g2_font_wqy12_t_gb2312, BinaryColor::On);
+        self.screen.clear(BinaryColor::Off).unwrap();
+
+        let hours = state
+            .weather
+            .as_ref()
+            .map(|weather| weather.hourly.as_slice())
+            .unwrap_or_default();
+        if hours.len() < 2 {
+            Text::new("暂无逐小时预报", Point::new(22, 26), style.clone())
+                .draw(&mut self.screen)
+                .unwrap();
+            self.screen.flush().unwrap();
+            return;
+        }
+
+        let max = hours.iter().map(|h| h.temperature).fold(f32::MIN, f32::max);
+        let min = hours.iter().map(|h| h.temperature).fold(f32::MAX, f32::min);
+        // 温度没有变化时画在中间
+        let range = (max - min).max(1.0);
+        let x_of = |i: usize| CHART_LEFT + i as i32 * (CHART_WIDTH - 1) / (hours.len() as i32 - 1);
+
+        let points: Vec<Point> = hours
+            .iter()
+            .enumerate()
+            .map(|(i, hour)| {
+                let offset = (max - hour.temperature) / range * (TEMPERATURE_HEIGHT - 1) as f32;
+                Point::new(x_of(i), TEMPERATURE_TOP + offset.round() as i32)
+            })
+            .collect();
+        Polyline::new(&points)
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+            .draw(&mut self.screen)
+            .unwrap();
+
+        let bar_width = (CHART_WIDTH / hours.len() as i32 - 1).max(1) as u32;
+        for (i, hour) in hours.iter().enumerate() {
+            let Some(probability) = hour.precipitation_probability.filter(|p| *p > 0) else {
+                continue;
+            };
+            let height = (probability.min(100) as i32 * PRECIPITATION_HEIGHT / 100).max(1);
+            Rectangle::new(
+                Point::new(
+                    x_of(i).min(CHART_LEFT + CHART_WIDTH - bar_width as i32),
+                    PRECIPITATION_TOP + PRECIPITATION_HEIGHT - height,
+                ),
+                Size::new(bar_width, height as u32),
+            )
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(&mut self.screen)
+            .unwrap();
+        }
+        // 降水柱的基线
+        Line::new(
+            Point::new(CHART_LEFT, PRECIPITATION_TOP + PRECIPITATION_HEIGHT),
+            Point::new(CHART_LEFT + CHART_WIDTH - 1, PRECIPITATION_TOP + PRECIPITATION_HEIGHT),
+        )
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+        .draw(&mut self.screen)
+        .unwrap();
+
+        let max_probability = hours
+            .iter()
+            .filter_map(|h| h.precipitation_probability)
+            .max();
+        let mut labels = vec![
+            (format!("{:.0}°", max), Point::new(0, 0)),
+            (format!("{:.0}°", min), Point::new(0, 18)),
+            (
+                max_probability.map_or(String::from("--"), |p| format!("{}%", p)),
+                Point::new(0, 38),
+            ),
+        ];
+        // 开始、中间和结束的时间
+        let last = hours.len() - 1;
+        for (i, x) in [(0, CHART_LEFT), (last / 2, CHART_LEFT + 35), (last, CHART_LEFT + 70)] {
+            labels.push((hours[i].time.clone(), Point::new(x, 52)));
+        }
+        for (label, position) in &labels {
+            Text::with_baseline(label, *position, style.clone(), Baseline::Top)
+                .draw(&mut self.screen)
+                .unwrap();
+        }
+
+        self.screen.flush().unwrap();
+    }
+
+    /// AQI、分级、PM2.5/PM10，底部的分级条上用三角标出当前位置
+    pub fn show_air_quality(&mut self, state: State) {
+        let style_12 = U8g2TextStyle::new(u8g2_font_wqy12_t_gb2312, BinaryColor::On);
+        let style_16 = U8g2TextStyle::new(u8g2_font_wqy16_t_gb2312, BinaryColor::On);
+        self.screen.clear(BinaryColor::Off).unwrap();
+
+        let air = state.weather.as_ref().and_then(|w| w.air_quality.as_ref());
+        let Some(air) = air.filter(|a| a.aqi.is_some() || !a.category.is_empty()) else {
+            Text::new("暂无空气质量数据", Point::new(16, 26), style_12.clone())
+                .draw(&mut self.screen)
+                .unwrap();
+            self.screen.flush().unwrap();
+            return;
+        };
+
+        let category = match (air.category.as_str(), air.aqi) {
+            ("", Some(aqi)) => AirQuality::category_for(aqi),
+            (category, _) => category,
+        };
+        let aqi = air.aqi.map_or(String::from("--"), |aqi| aqi.to_string());
+        let pollutant =
+            |value: Option<f32>| value.map_or(String::from("--"), |v| format!("{:.0}", v));
+        let lines = [
+            (String::from("空气质量"), Point::new(0, 0), style_12.clone()),
+            (format!("AQI {} {}", aqi, category), Point::new(0, 13), style_16.clone()),
+            (
+                format!("PM2.5 {}  PM10 {}", pollutant(air.pm25), pollutant(air.pm10)),
+                Point::new(0, 31),
+                style_12.clone(),
+            ),
+        ];
+        for (text, position, style) in lines {
+            Text::with_baseline(&text, position, style, Baseline::Top)
+                .draw(&mut self.screen)
+                .unwrap();
+        }
+
+        let levels = AirQuality::LEVELS.len() as i32;
+        let segment_start = |level: i32| level * 128 / levels;
+        for level in 0..levels {
+            let (start, end) = (segment_start(level), segment_start(level + 1));
+            let pixels = (start..end).flat_map(|x| {
+                (AQI_BAR_TOP..AQI_BAR_TOP + AQI_BAR_HEIGHT).filter_map(move |y| {
+                    // 每段之间空一列
+                    let on = x < end - 1 && aqi_pattern(level as usize, x, y);
+                    on.then_some(Pixel(Point::new(x, y), BinaryColor::On))
+                })
+            });
+            self.screen.draw_iter(pixels).unwrap();
+            Rectangle::new(
+                Point::new(start, AQI_BAR_TOP),
+                Size::new((end - start - 1) as u32, AQI_BAR_HEIGHT as u32),
+            )
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+            .draw(&mut self.screen)
+            .unwrap();
+        }
+
+        if let Some(aqi) = air.aqi {
+            // 在所在分级内按比例定位
+            let level = AirQuality::level(aqi);
+            let lower = if level == 0 { 0 } else { AirQuality::LEVELS[level - 1] };
+            let upper = AirQuality::LEVELS[level];
+            let fraction = (aqi.min(upper) - lower) as i32;
+            let (start, end) = (segment_start(level as i32), segment_start(level as i32 + 1));
+            let x = start + fraction * (end - start - 1) / (upper - lower) as i32;
+            let top = AQI_BAR_TOP + AQI_BAR_HEIGHT + 1;
+            Triangle::new(Point::new(x, top), Point::new(x - 3, 63), Point::new(x + 3, 63))
+                .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+                .draw(&mut self.screen)
+                .unwrap();
+        }
+
+        self.screen.flush().unwrap();
+    }
+
+    /// 预警横幅，inverted 交替变化实现闪烁
+    pub fn show_alert(&mut self, alert: &WeatherAlert, inverted: bool) {
+        let style_12 = U8g2TextStyle::new(u8g2_font_wqy12_t_gb2312, BinaryColor::On);
+        let style_16 = U8g2TextStyle::new(u8g2_font_wqy16_t_gb2312, BinaryColor::On);
+        self.screen.clear(BinaryColor::Off).unwrap();
+
+        let banner = Rectangle::new(Point::zero(), Size::new(128, 18));
+        let banner_style = if inverted {
+            PrimitiveStyle::with_fill(BinaryColor::On)
+        } else {
+            PrimitiveStyle::with_stroke(BinaryColor::On, 1)
+        };
+        banner.into_styled(banner_style).draw(&mut self.screen).unwrap();
+        let banner_text_color = if inverted {
+            BinaryColor::Off
+        } else {
+            BinaryColor::On
+        };
+        Text::with_baseline(
+            "! 气象预警 !",
+            Point::new(28, 1),
+            U8g2TextStyle::new(u8g2_font_wqy16_t_gb2312, banner_text_color),
+            Baseline::Top,
+        )
+        .draw(&mut self.screen)
+        .unwrap();
+
+        // 16 号字一行约 8 个汉字，12 号字约 10 个
+        let event = truncate_chars(&alert.event, 8);
+        let sender = truncate_chars(&alert.sender, 10);
+        let lines = [
+            (event.as_str(), Point::new(0, 21), style_16),
+            (sender.as_str(), Point::new(0, 39), style_12.clone()),
+            ("按任意键确认", Point::new(0, 52), style_12),
+        ];
+        for (text, position, style) in lines {
+            Text::with_baseline(text, position, style, Baseline::Top)
+                .draw(&mut self.screen)
+                .unwrap();
+        }
+
+        self.screen.flush().unwrap();
+    }
+
+    /// 左边是加入热点的二维码，右边提示配网地址
+    pub fn show_wifi_config(&mut self, ap_ssid: &str, portal_ip: &str) {
+        let style = U8g2TextStyle::new(u8g2_font_wqy12_t_gb2312, BinaryColor::On);
+        self.screen.clear(BinaryColor::Off).unwrap();
+
+        let qr_text = format!("WIFI:S:{};T:nopass;;", ap_ssid);
+        self.draw_qr_code(&qr_text, Point::new(0, 0));
+
+        let tips = Chain::new(Text::new("扫码连接", Point::zero(), style.clone()))
+            .append(Text::new("热点后访问", Point::zero(), style.clone()))
+            .append(Text::new(portal_ip, Point::zero(), style.clone()));
+        let layout = LinearLayout::vertical(tips)
+            .with_spacing(DistributeFill(48))
+            .arrange();
+        layout
+            .translate(Point::new(56, 8))
+            .draw(&mut self.screen)
+            .unwrap();
+
+        self.screen.flush().unwrap();
+    }
+
+    fn draw_qr_code(&mut self, text: &str, origin: Point) {
+        let qr = QrCode::encode_text(text, QrCodeEcc::Low).unwrap();
+        // 放大两倍仍能放进 64 像素高度时才放大
+        let scale = if qr.size() * 2 <= 64 { 2 } else { 1 };
+        for y in 0..qr.size() {
+            for x in 0..qr.size() {
+                if qr.get_module(x, y) {
+                    Rectangle::new(
+                        origin + Point::new(x * scale, y * scale),
+                        Size::new(scale as u32, scale as u32),
+                    )
+                    .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+                    .draw(&mut self.screen)
+                    .unwrap();
+                }
+            }
+        }
     }
 }
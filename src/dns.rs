@@ -0,0 +1,71 @@
+use std::{
+    net::{Ipv4Addr, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use log::{info, warn};
+
+/// 配网用的 DNS 劫持：所有 A 记录查询都指向 SoftAP 地址，
+/// 手机连上热点后会自动弹出配网页面。enabled 为 false 时（STA 已连接、
+/// 热点已关闭）不再应答，避免劫持局域网内的查询
+pub fn start_captive_dns(ip: Ipv4Addr, enabled: Arc<AtomicBool>) -> anyhow::Result<()> {
+    let socket = UdpSocket::bind("0.0.0.0:53")?;
+    thread::Builder::new()
+        .stack_size(4096)
+        .spawn(move || {
+            info!("Captive DNS started, answering with {}", ip);
+            let mut buffer = [0u8; 512];
+            loop {
+                let (len, src) = match socket.recv_from(&mut buffer) {
+                    Ok(received) => received,
+                    Err(e) => {
+                        warn!("Captive DNS recv failed: {:?}", e);
+                        continue;
+                    }
+                };
+                if !enabled.load(Ordering::Relaxed) {
+                    continue;
+                }
+                if let Some(response) = build_response(&buffer[..len], ip) {
+                    if let Err(e) = socket.send_to(&response, src) {
+                        warn!("Captive DNS send failed: {:?}", e);
+                    }
+                }
+            }
+        })?;
+    Ok(())
+}
+
+fn build_response(query: &[u8], ip: Ipv4Addr) -> Option<Vec<u8>> {
+    // header is 12 bytes, only answer standard queries
+    if query.len() < 12 || query[2] & 0x80 != 0 {
+        return None;
+    }
+    // skip QNAME labels, then QTYPE + QCLASS
+    let mut pos = 12;
+    while pos < query.len() && query[pos] != 0 {
+        pos += query[pos] as usize + 1;
+    }
+    let end = pos + 5;
+    if end > query.len() {
+        return None;
+    }
+    let qtype = u16::from_be_bytes([query[pos + 1], query[pos + 2]]);
+    let answers = if qtype == 1 { 1 } else { 0 };
+
+    let mut response = Vec::with_capacity(end + 16);
+    response.extend_from_slice(&query[..2]);
+    response.extend_from_slice(&[0x81, 0x80]);
+    response.extend_from_slice(&[0x00, 0x01, 0x00, answers, 0x00, 0x00, 0x00, 0x00]);
+    response.extend_from_slice(&query[12..end]);
+    if answers > 0 {
+        // name pointer to the question, type A, class IN, ttl 60s, 4 bytes
+        response.extend_from_slice(&[0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x04]);
+        response.extend_from_slice(&ip.octets());
+    }
+    Some(response)
+}
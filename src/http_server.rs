@@ -1,94 +1,829 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::HashMap,
+    net::Ipv4Addr,
+    sync::{mpsc::Sender, Arc, Mutex},
+    thread,
+};
 
-use esp_idf_svc::http::{
-    server::{Configuration, EspHttpConnection, EspHttpServer, Request},
-    Method,
+use esp_idf_svc::{
+    http::{
+        server::{Configuration, EspHttpConnection, EspHttpServer, Request},
+        Method,
+    },
+    io::{Read, Write},
+    mdns::EspMdns,
 };
-use log::info;
+use log::{info, warn};
 
-use crate::{config::parse_form_data, oled::OLed};
+use serde::Serialize;
+
+use crate::{
+    app::{AppPage, DisplayMessage},
+    config::{parse_form, parse_form_data, ConfigStore},
+    mdns::restart_mdns,
+    state::State,
+    weather::{
+        validate_pem, Location, ProviderKind, WeatherAlert, WeatherClient, WeatherConfig,
+        WeatherData,
+    },
+    wifi::{
+        EnterpriseAuth, SavedNetwork, StaticIp, WiFiManager, WifiCredentials, WifiInfo,
+        WifiStatus,
+    },
+};
+
+const INDEX_HTML: &str = include_str!("./assets/index.html");
 
-/// 共享 Wi-Fi 配置信息
-#[derive(Clone)]
-pub struct WifiConfig {
-    pub ssid: String,
-    pub password: String,
-}
 pub fn start_http_server(
-    server: Arc<Mutex<EspHttpServer<'static>>>,
-    wifi_config: Arc<Mutex<Option<WifiConfig>>>,
-    display: Arc<Mutex<OLed<'static>>>,
-    ip: String,
-) {
-    let mut server = server.lock().unwrap();
-    
-    // let mut server: EspHttpServer<'_> = EspHttpServer::new(&Configuration::default()).unwrap();
-    let display_clone = display.clone();
-    server
-        .fn_handler(
-            "/",
-            embedded_svc::http::Method::Get,
-            index_handler(display_clone),
-        )
-        .unwrap();
+    wifi: Arc<Mutex<WiFiManager<'static>>>,
+    weather_client: Arc<Mutex<WeatherClient>>,
+    config: Arc<Mutex<ConfigStore>>,
+    weather_refresh_tx: Sender<()>,
+    state: Arc<Mutex<State>>,
+    display_tx: Sender<DisplayMessage>,
+    mdns: Arc<Mutex<Option<EspMdns>>>,
+) -> anyhow::Result<EspHttpServer<'static>> {
+    let mut server = EspHttpServer::new(&Configuration {
+        // 城市搜索会在处理请求时访问 https 接口
+        stack_size: 12288,
+        uri_match_wildcard: true,
+        ..Default::default()
+    })?;
+
+    server.fn_handler("/", Method::Get, index_handler())?;
+
+    // 设备状态
+    server.fn_handler("/api/status", Method::Get, status_handler(state.clone()))?;
+
+    // 最近一次获取的天气
+    server.fn_handler("/api/weather", Method::Get, weather_handler(state.clone()))?;
+
+    // 天气源配置
+    server.fn_handler(
+        "/api/weather/config",
+        Method::Get,
+        weather_config_handler(weather_client.clone()),
+    )?;
+    server.fn_handler(
+        "/api/weather/config",
+        Method::Post,
+        set_weather_config_handler(
+            weather_client.clone(),
+            config.clone(),
+            weather_refresh_tx.clone(),
+        ),
+    )?;
+    server.fn_handler(
+        "/api/weather/pin",
+        Method::Post,
+        set_pinned_cert_handler(weather_client.clone(), config.clone()),
+    )?;
+    server.fn_handler(
+        "/api/weather/refresh",
+        Method::Post,
+        refresh_weather_handler(weather_refresh_tx.clone()),
+    )?;
+    server.fn_handler(
+        "/api/location/search",
+        Method::Get,
+        location_search_handler(weather_client.clone()),
+    )?;
+    server.fn_handler(
+        "/api/weather/location",
+        Method::Post,
+        set_location_handler(
+            weather_client.clone(),
+            config.clone(),
+            weather_refresh_tx.clone(),
+        ),
+    )?;
+
+    // 附近的 Wi-Fi 网络列表
+    server.fn_handler("/scan", Method::Get, scan_handler(wifi.clone()))?;
+
+    // 已保存网络的管理
+    server.fn_handler("/api/networks", Method::Get, networks_handler(wifi.clone()))?;
+    server.fn_handler("/api/networks", Method::Post, add_network_handler(wifi.clone()))?;
+    server.fn_handler(
+        "/api/networks/remove",
+        Method::Post,
+        remove_network_handler(wifi.clone()),
+    )?;
+    server.fn_handler(
+        "/api/networks/order",
+        Method::Post,
+        reorder_networks_handler(wifi.clone()),
+    )?;
+
+    // DHCP 和 mDNS 主机名
+    server.fn_handler("/api/hostname", Method::Get, hostname_handler(wifi.clone()))?;
+    server.fn_handler(
+        "/api/hostname",
+        Method::Post,
+        set_hostname_handler(wifi.clone(), mdns),
+    )?;
+
+    // 气象预警
+    server.fn_handler("/api/alerts", Method::Get, alerts_handler(state.clone()))?;
+    server.fn_handler(
+        "/api/alerts/ack",
+        Method::Post,
+        acknowledge_alerts_handler(display_tx.clone()),
+    )?;
 
     // 处理 Wi-Fi 连接请求
-    let wifi_config_clone = wifi_config.clone();
-    let display_clone = display.clone();
-    server
-        .fn_handler(
-            "/connect",
-            Method::Post,
-            connect_handler(wifi_config_clone, display_clone),
+    server.fn_handler("/connect", Method::Post, connect_handler(wifi, display_tx))?;
+
+    // 其余请求（手机的联网检测等）都重定向到配网页面
+    server.fn_handler("/*", Method::Get, redirect_handler())?;
+
+    info!("HTTP server started");
+    Ok(server)
+}
+
+fn index_handler() -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        let mut response = req.into_response(200, None, &[("Content-Type", "text/html; charset=utf-8")])?;
+        response.write_all(INDEX_HTML.as_bytes())?;
+        anyhow::Ok(())
+    }
+}
+
+fn redirect_handler() -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        req.into_response(302, Some("Found"), &[("Location", "/")])?;
+        anyhow::Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct WifiStatusResponse {
+    status: WifiStatus,
+    message: String,
+    #[serde(flatten)]
+    info: WifiInfo,
+}
+
+#[derive(Serialize)]
+struct StatusResponse {
+    wifi: WifiStatusResponse,
+}
+
+fn status_handler(
+    state: Arc<Mutex<State>>,
+) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        let (status, info) = state.lock().unwrap().get_wifi_status();
+        let response = StatusResponse {
+            wifi: WifiStatusResponse {
+                message: status.to_string(),
+                status,
+                info,
+            },
+        };
+        write_json(req, &response)
+    }
+}
+
+#[derive(Serialize)]
+struct WeatherResponse {
+    weather: Option<WeatherData>,
+    /// 距离上次成功获取的秒数
+    age: Option<u64>,
+    stale: bool,
+    /// 重启后从缓存恢复，还没有重新获取过
+    cached: bool,
+    error: Option<String>,
+}
+
+fn weather_handler(
+    state: Arc<Mutex<State>>,
+) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        let response = {
+            let state = state.lock().unwrap();
+            WeatherResponse {
+                weather: state.weather.clone(),
+                age: state.weather_age().map(|age| age.as_secs()),
+                stale: state.is_weather_stale(),
+                cached: state.weather_cache.is_some(),
+                error: state.weather_error.clone(),
+            }
+        };
+        write_json(req, &response)
+    }
+}
+
+#[derive(Serialize)]
+struct AlertResponse {
+    #[serde(flatten)]
+    alert: WeatherAlert,
+    acknowledged: bool,
+    expired: bool,
+}
+
+fn alerts_handler(
+    state: Arc<Mutex<State>>,
+) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        let alerts: Vec<AlertResponse> = {
+            let state = state.lock().unwrap();
+            state
+                .alerts
+                .iter()
+                .map(|alert| AlertResponse {
+                    alert: alert.clone(),
+                    acknowledged: state.acknowledged_alerts.contains(&alert.id),
+                    expired: alert.is_expired(),
+                })
+                .collect()
+        };
+        write_json(req, &alerts)
+    }
+}
+
+/// 确认所有预警，与在设备上按键效果相同
+fn acknowledge_alerts_handler(
+    display_tx: Sender<DisplayMessage>,
+) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        display_tx.send(DisplayMessage::AcknowledgeAlerts)?;
+        req.into_ok_response()?.write_all(b"OK")?;
+        anyhow::Ok(())
+    }
+}
+
+/// 不返回 API key 本身，只告诉页面是否已经设置
+#[derive(Serialize)]
+struct WeatherConfigResponse<'a> {
+    provider: ProviderKind,
+    has_api_key: bool,
+    /// 和风天气预警的 key 同样只返回是否已设置
+    has_qweather_key: bool,
+    qweather_host: &'a str,
+    location: &'a Location,
+    /// 固定了证书的天气源，不返回证书内容
+    pinned: Vec<ProviderKind>,
+    refresh_minutes: u32,
+    /// 当前天气源允许的最小刷新间隔（分钟）
+    min_refresh_minutes: u64,
+}
+
+fn weather_config_handler(
+    weather_client: Arc<Mutex<WeatherClient>>,
+) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        let client = weather_client.lock().unwrap();
+        let config = client.config();
+        write_json(
+            req,
+            &WeatherConfigResponse {
+                provider: config.provider,
+                has_api_key: !config.api_key.is_empty(),
+                has_qweather_key: !config.qweather_key.is_empty(),
+                qweather_host: &config.qweather_host,
+                location: &config.location,
+                pinned: config.pinned.iter().copied().collect(),
+                refresh_minutes: config.refresh_minutes,
+                min_refresh_minutes: config.provider.min_refresh_interval().as_secs() / 60,
+            },
         )
-        .unwrap();
-    display.lock().unwrap().clear();
-    display.lock().unwrap().draw_qr_code(format!("http://{}", ip).as_str());
+    }
+}
+
+/// 表单字段：provider (sojson / open-meteo / open-weather-map), api_key, refresh_minutes,
+/// qweather_key, qweather_host；两个 key 留空时保留原来的值，qweather_key 填 "-" 时清除
+fn set_weather_config_handler(
+    weather_client: Arc<Mutex<WeatherClient>>,
+    config: Arc<Mutex<ConfigStore>>,
+    weather_refresh_tx: Sender<()>,
+) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |mut req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        let form = parse_form(&read_body(&mut req)?);
+        let mut weather_config: WeatherConfig = weather_client.lock().unwrap().config().clone();
+        if let Some(provider) = form.get("provider") {
+            match serde_json::from_value(serde_json::Value::String(provider.clone())) {
+                Ok(provider) => weather_config.provider = provider,
+                Err(_) => {
+                    req.into_status_response(400)?
+                        .write_all(format!("Unknown provider: {}", provider).as_bytes())?;
+                    return anyhow::Ok(());
+                }
+            }
+        }
+        if let Some(api_key) = form.get("api_key").map(|k| k.trim()).filter(|k| !k.is_empty()) {
+            weather_config.api_key = api_key.to_string();
+        }
+        match form.get("qweather_key").map(|k| k.trim()) {
+            Some("-") => weather_config.qweather_key.clear(),
+            Some(key) if !key.is_empty() => weather_config.qweather_key = key.to_string(),
+            _ => {}
+        }
+        if let Some(host) = form.get("qweather_host") {
+            weather_config.qweather_host = host.trim().to_string();
+        }
+        let refresh_minutes = form
+            .get("refresh_minutes")
+            .map(|m| m.trim())
+            .filter(|m| !m.is_empty());
+        if let Some(minutes) = refresh_minutes {
+            match minutes.parse::<u32>() {
+                Ok(minutes) if (1..=24 * 60).contains(&minutes) => {
+                    weather_config.refresh_minutes = minutes
+                }
+                _ => {
+                    req.into_status_response(400)?.write_all("无效的刷新间隔".as_bytes())?;
+                    return anyhow::Ok(());
+                }
+            }
+        }
+        config
+            .lock()
+            .unwrap()
+            .save(ConfigStore::WEATHER, &weather_config)?;
+        weather_client.lock().unwrap().set_config(weather_config);
+        let _ = weather_refresh_tx.send(());
+        req.into_ok_response()?.write_all(b"OK")?;
+        anyhow::Ok(())
+    }
 }
 
-fn index_handler(
-    display: Arc<Mutex<OLed<'static>>>,
+/// 表单字段：provider, cert（CA 证书的 PEM）；cert 留空时取消固定，改用 ESP-IDF 证书包
+fn set_pinned_cert_handler(
+    weather_client: Arc<Mutex<WeatherClient>>,
+    config: Arc<Mutex<ConfigStore>>,
+) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |mut req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        // URL 编码后的 PEM 证书可能有好几 KB
+        let body = match read_body_limited(&mut req, 8 * 1024) {
+            Ok(body) => body,
+            Err(e) if e.is::<BodyTooLarge>() => {
+                req.into_status_response(413)?.write_all("证书太大".as_bytes())?;
+                return anyhow::Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        let form = parse_form(&body);
+        let provider: Option<ProviderKind> = form
+            .get("provider")
+            .and_then(|p| serde_json::from_value(serde_json::Value::String(p.clone())).ok());
+        let Some(provider) = provider else {
+            req.into_status_response(400)?.write_all(b"Unknown provider")?;
+            return anyhow::Ok(());
+        };
+        let cert = match form.get("cert").map(|c| c.trim()).filter(|c| !c.is_empty()) {
+            // 单个 NVS 字符串最长 4000 字节（含结尾的 \0）
+            Some(cert) if cert.len() >= 4000 => {
+                req.into_status_response(413)?.write_all("证书太大".as_bytes())?;
+                return anyhow::Ok(());
+            }
+            Some(cert) => match validate_pem(cert) {
+                Ok(()) => Some(cert.to_string()),
+                Err(e) => {
+                    req.into_status_response(400)?
+                        .write_all(format!("证书必须是 PEM 格式: {}", e).as_bytes())?;
+                    return anyhow::Ok(());
+                }
+            },
+            None => None,
+        };
+        // 证书单独存放，配置中只记录哪些天气源固定了证书
+        match &cert {
+            Some(cert) => config.lock().unwrap().save_str(provider.cert_key(), cert)?,
+            None => config.lock().unwrap().remove(provider.cert_key())?,
+        }
+        let weather_config = {
+            let mut client = weather_client.lock().unwrap();
+            client.set_pinned_cert(provider, cert);
+            client.config().clone()
+        };
+        config
+            .lock()
+            .unwrap()
+            .save(ConfigStore::WEATHER, &weather_config)?;
+        req.into_ok_response()?.write_all(b"OK")?;
+        anyhow::Ok(())
+    }
+}
+
+/// 唤醒天气线程立即刷新，不受刷新间隔限制
+fn refresh_weather_handler(
+    weather_refresh_tx: Sender<()>,
 ) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
     move |req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
-        let html = r#"
-          <html>
-          <body>
-              <h2>Wi-Fi 设置</h2>
-              <form action="/connect" method="post">
-                  SSID: <input type="text" name="ssid"><br>
-                  密码: <input type="password" name="password"><br>
-                  <input type="submit" value="连接">
-              </form>
-          </body>
-          </html>
-          "#;
-        let mut response = req.into_ok_response()?;
-        response.write(html.as_bytes())?;
-        display.lock().unwrap().clear();
-        display.lock().unwrap().show_message("");
+        let _ = weather_refresh_tx.send(());
+        req.into_ok_response()?.write_all(b"OK")?;
         anyhow::Ok(())
     }
 }
 
-fn connect_handler(
-    wifi: Arc<Mutex<Option<WifiConfig>>>,
-    display: Arc<Mutex<OLed<'static>>>,
+/// 查询参数：q，用当前天气源的地理编码接口搜索城市
+fn location_search_handler(
+    weather_client: Arc<Mutex<WeatherClient>>,
+) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        let query = query_params(req.uri());
+        let Some(q) = query.get("q").map(|q| q.trim()).filter(|q| !q.is_empty()) else {
+            req.into_status_response(400)?.write_all(b"Missing q")?;
+            return anyhow::Ok(());
+        };
+        let candidates = weather_client.lock().unwrap().search_location(q)?;
+        write_json(req, &candidates)
+    }
+}
+
+/// 表单字段：name, code (sojson 城市代码), latitude, longitude；未提供的字段保持不变，
+/// 只修改经纬度时清除原来的城市代码
+fn set_location_handler(
+    weather_client: Arc<Mutex<WeatherClient>>,
+    config: Arc<Mutex<ConfigStore>>,
+    weather_refresh_tx: Sender<()>,
+) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |mut req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        let form = parse_form(&read_body(&mut req)?);
+        let mut weather_config: WeatherConfig = weather_client.lock().unwrap().config().clone();
+        let location = &mut weather_config.location;
+        let field = |name: &str| form.get(name).map(|v| v.trim()).filter(|v| !v.is_empty());
+        if let Some(name) = field("name") {
+            location.name = name.to_string();
+        }
+        if let Some(code) = field("code") {
+            if !code.chars().all(|c| c.is_ascii_digit()) {
+                req.into_status_response(400)?.write_all("无效的城市代码".as_bytes())?;
+                return anyhow::Ok(());
+            }
+            location.code = code.to_string();
+        }
+        let latitude = field("latitude").map(str::parse::<f32>);
+        let longitude = field("longitude").map(str::parse::<f32>);
+        match (latitude, longitude) {
+            (None, None) => {}
+            (Some(Ok(latitude)), Some(Ok(longitude)))
+                if (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude) =>
+            {
+                // 只改了经纬度时原来的城市代码指向别的城市，不能再用
+                let moved = latitude != location.latitude || longitude != location.longitude;
+                if moved && field("code").is_none() {
+                    location.code.clear();
+                }
+                location.latitude = latitude;
+                location.longitude = longitude;
+            }
+            _ => {
+                req.into_status_response(400)?.write_all("无效的经纬度".as_bytes())?;
+                return anyhow::Ok(());
+            }
+        }
+        info!("Weather location updated: {:?}", location);
+        config
+            .lock()
+            .unwrap()
+            .save(ConfigStore::WEATHER, &weather_config)?;
+        weather_client.lock().unwrap().set_config(weather_config);
+        let _ = weather_refresh_tx.send(());
+        req.into_ok_response()?.write_all(b"OK")?;
+        anyhow::Ok(())
+    }
+}
+
+fn scan_handler(
+    wifi: Arc<Mutex<WiFiManager<'static>>>,
 ) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
-    return move |mut req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
-        let mut buffer = [0u8; 256];
-        let len = req.read(&mut buffer).unwrap_or(0);
-        let body = String::from_utf8_lossy(&buffer[..len]);
+    move |req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        let networks = wifi.lock().unwrap().scan()?;
+        info!("scan wifi: {} networks", networks.len());
+        write_json(req, &networks)
+    }
+}
+
+/// 已保存网络的列表，不返回密码
+#[derive(Serialize)]
+struct SavedNetworks<'a> {
+    current: Option<&'a str>,
+    networks: Vec<SavedNetworkEntry<'a>>,
+}
 
-        if let Some((ssid, password)) = parse_form_data(&body) {
-            *wifi.lock().unwrap() = Some(WifiConfig { ssid, password });
-            info!("收到 Wi-Fi 连接信息");
+#[derive(Serialize)]
+struct SavedNetworkEntry<'a> {
+    ssid: &'a str,
+    priority: u8,
+}
 
-            let mut display = display.lock().unwrap();
-            display.show_message("Wi-Fi Configured!");
+fn networks_handler(
+    wifi: Arc<Mutex<WiFiManager<'static>>>,
+) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        let wifi = wifi.lock().unwrap();
+        let networks = SavedNetworks {
+            current: wifi.current_network().map(|n| n.ssid.as_str()),
+            networks: wifi
+                .networks()
+                .iter()
+                .map(|n| SavedNetworkEntry {
+                    ssid: &n.ssid,
+                    priority: n.priority,
+                })
+                .collect(),
+        };
+        write_json(req, &networks)
+    }
+}
+
+/// 表单字段：ssid, password, priority，以及可选的静态 IP 和 DNS 字段
+fn add_network_handler(
+    wifi: Arc<Mutex<WiFiManager<'static>>>,
+) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |mut req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        let mut form = parse_form(&read_body(&mut req)?);
+        let addresses = parse_static_ip(&form).and_then(|ip| Ok((ip, parse_dns(&form)?)));
+        let (static_ip, dns) = match addresses {
+            Ok(addresses) => addresses,
+            Err(e) => {
+                req.into_status_response(400)?.write_all(e.to_string().as_bytes())?;
+                return anyhow::Ok(());
+            }
+        };
+        let Some(ssid) = form.remove("ssid").filter(|ssid| !ssid.is_empty()) else {
+            req.into_status_response(400)?.write_all("缺少 Wi-Fi 名称".as_bytes())?;
+            return anyhow::Ok(());
+        };
+        let password = form.remove("password").unwrap_or_default();
+        let enterprise = parse_enterprise(&form);
+        if let Err(e) = check_credentials(&ssid, &password, enterprise.is_some()) {
+            req.into_status_response(400)?.write_all(e.to_string().as_bytes())?;
+            return anyhow::Ok(());
         }
+        let network = SavedNetwork {
+            ssid,
+            password,
+            priority: form
+                .get("priority")
+                .and_then(|p| p.parse().ok())
+                .unwrap_or(0),
+            static_ip,
+            dns,
+            enterprise,
+        };
+        wifi.lock().unwrap().add_network(network)?;
+        req.into_ok_response()?.write_all(b"OK")?;
+        anyhow::Ok(())
+    }
+}
 
-        req.into_ok_response()?
-            .write(b"Wait ESP32 connecting Wi-Fi...")?;
+/// 表单字段：ssid
+fn remove_network_handler(
+    wifi: Arc<Mutex<WiFiManager<'static>>>,
+) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |mut req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        let form = parse_form(&read_body(&mut req)?);
+        let ssid = form.get("ssid").map(String::as_str).unwrap_or("");
+        if wifi.lock().unwrap().remove_network(ssid)? {
+            req.into_ok_response()?.write_all(b"OK")?;
+        } else {
+            req.into_status_response(404)?.write_all(b"Not found")?;
+        }
         anyhow::Ok(())
+    }
+}
+
+/// 请求体为按优先级从高到低排列的 SSID JSON 数组
+fn reorder_networks_handler(
+    wifi: Arc<Mutex<WiFiManager<'static>>>,
+) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |mut req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        let Ok(order) = serde_json::from_str::<Vec<String>>(&read_body(&mut req)?) else {
+            req.into_status_response(400)?.write_all(b"Expected a JSON array of SSIDs")?;
+            return anyhow::Ok(());
+        };
+        wifi.lock().unwrap().reorder_networks(&order)?;
+        req.into_ok_response()?.write_all(b"OK")?;
+        anyhow::Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct HostnameResponse<'a> {
+    hostname: &'a str,
+}
+
+fn hostname_handler(
+    wifi: Arc<Mutex<WiFiManager<'static>>>,
+) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        let wifi = wifi.lock().unwrap();
+        write_json(
+            req,
+            &HostnameResponse {
+                hostname: wifi.hostname(),
+            },
+        )
+    }
+}
+
+/// 表单字段：hostname；mDNS 立即使用新主机名，DHCP 下次连接时生效
+fn set_hostname_handler(
+    wifi: Arc<Mutex<WiFiManager<'static>>>,
+    mdns: Arc<Mutex<Option<EspMdns>>>,
+) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |mut req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        let form = parse_form(&read_body(&mut req)?);
+        let hostname = form.get("hostname").map(|h| h.trim()).unwrap_or("");
+        let result = {
+            let mut wifi = wifi.lock().unwrap();
+            wifi.set_hostname(hostname).map(|()| wifi.device_id())
+        };
+        match result {
+            Ok(device_id) => {
+                let restarted =
+                    device_id.and_then(|device_id| restart_mdns(&mdns, hostname, &device_id));
+                if let Err(e) = restarted {
+                    warn!("mDNS failed to restart: {:?}", e);
+                }
+                req.into_ok_response()?.write_all(b"OK")?
+            }
+            Err(e) => req
+                .into_status_response(400)?
+                .write_all(e.to_string().as_bytes())?,
+        }
+        anyhow::Ok(())
+    }
+}
+
+/// 表单中的静态 IP 字段：ip, gateway, netmask；ip 为空时使用 DHCP
+fn parse_static_ip(form: &HashMap<String, String>) -> anyhow::Result<Option<StaticIp>> {
+    let field = |name: &str| form.get(name).map(|v| v.trim()).filter(|v| !v.is_empty());
+    let Some(ip) = field("ip") else {
+        return Ok(None);
+    };
+    let ip: Ipv4Addr = ip
+        .parse()
+        .map_err(|_| anyhow::anyhow!("无效的 IP 地址: {}", ip))?;
+    let gateway: Ipv4Addr = field("gateway")
+        .ok_or_else(|| anyhow::anyhow!("缺少网关地址"))?
+        .parse()
+        .map_err(|_| anyhow::anyhow!("无效的网关地址"))?;
+    // 支持前缀长度 24 或掩码 255.255.255.0 两种写法，掩码的 1 必须连续且不能为 0
+    let netmask = field("netmask").unwrap_or("24");
+    let invalid = || anyhow::anyhow!("无效的子网掩码: {}", netmask);
+    let netmask = match netmask.parse::<u8>() {
+        Ok(prefix) if (1..=32).contains(&prefix) => prefix,
+        Ok(_) => return Err(invalid()),
+        Err(_) => {
+            let mask = u32::from(netmask.parse::<Ipv4Addr>().map_err(|_| invalid())?);
+            if mask == 0 || mask.leading_ones() + mask.trailing_zeros() != 32 {
+                return Err(invalid());
+            }
+            mask.leading_ones() as u8
+        }
     };
+    Ok(Some(StaticIp {
+        ip,
+        gateway,
+        netmask,
+    }))
+}
+
+/// 表单字段：dns，与静态 IP 无关，留空使用 DHCP 分配的 DNS
+fn parse_dns(form: &HashMap<String, String>) -> anyhow::Result<Option<Ipv4Addr>> {
+    let Some(dns) = form.get("dns").map(|v| v.trim()).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    let dns = dns
+        .parse()
+        .map_err(|_| anyhow::anyhow!("无效的 DNS 地址: {}", dns))?;
+    Ok(Some(dns))
+}
+
+/// SSID 最长 32 字节，WPA 密码最长 64 字节；企业网络的密码通过 EAP 设置，不受此限制
+fn check_credentials(ssid: &str, password: &str, enterprise: bool) -> anyhow::Result<()> {
+    if ssid.len() > 32 {
+        anyhow::bail!("Wi-Fi 名称不能超过 32 字节");
+    }
+    if !enterprise && password.len() > 64 {
+        anyhow::bail!("Wi-Fi 密码不能超过 64 字节");
+    }
+    Ok(())
+}
+
+/// 表单字段：identity, username；填写了 username 即视为企业网络
+fn parse_enterprise(form: &HashMap<String, String>) -> Option<EnterpriseAuth> {
+    let username = form.get("username").map(|v| v.trim()).filter(|v| !v.is_empty())?;
+    let identity = form
+        .get("identity")
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .unwrap_or(username);
+    Some(EnterpriseAuth {
+        identity: identity.to_string(),
+        username: username.to_string(),
+    })
+}
+
+fn connect_handler(
+    wifi: Arc<Mutex<WiFiManager<'static>>>,
+    display_tx: Sender<DisplayMessage>,
+) -> impl Fn(Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    move |mut req: Request<&mut EspHttpConnection>| -> anyhow::Result<()> {
+        let body = read_body(&mut req)?;
+
+        let Some((ssid, password)) = parse_form_data(&body) else {
+            req.into_status_response(400)?.write_all("缺少 Wi-Fi 名称".as_bytes())?;
+            return anyhow::Ok(());
+        };
+        let form = parse_form(&body);
+        let addresses = parse_static_ip(&form).and_then(|ip| Ok((ip, parse_dns(&form)?)));
+        let (static_ip, dns) = match addresses {
+            Ok(addresses) => addresses,
+            Err(e) => {
+                req.into_status_response(400)?.write_all(e.to_string().as_bytes())?;
+                return anyhow::Ok(());
+            }
+        };
+        let enterprise = parse_enterprise(&form);
+        if let Err(e) = check_credentials(&ssid, &password, enterprise.is_some()) {
+            req.into_status_response(400)?.write_all(e.to_string().as_bytes())?;
+            return anyhow::Ok(());
+        }
+        info!("收到 Wi-Fi 连接信息: {}", ssid);
+        wifi.lock().unwrap().save_credentials(WifiCredentials {
+            ssid,
+            password,
+            static_ip,
+            dns,
+            enterprise,
+        })?;
+
+        req.into_ok_response()?
+            .write_all(b"Wait ESP32 connecting Wi-Fi...")?;
+
+        // 连接会阻塞几秒，放到单独线程里，先把响应发给手机
+        let wifi = wifi.clone();
+        let display_tx = display_tx.clone();
+        thread::Builder::new()
+            .stack_size(6000)
+            .spawn(move || match wifi.lock().unwrap().reconnect() {
+                Ok(()) => {
+                    display_tx
+                        .send(DisplayMessage::ShowPage(AppPage::Home))
+                        .unwrap();
+                }
+                Err(e) => warn!("Wi-Fi reconnect failed: {:?}", e),
+            })?;
+        anyhow::Ok(())
+    }
+}
+
+pub fn write_json<T: serde::Serialize>(
+    req: Request<&mut EspHttpConnection>,
+    value: &T,
+) -> anyhow::Result<()> {
+    let json = serde_json::to_string(value)?;
+    req.into_response(200, None, &[("Content-Type", "application/json")])?
+        .write_all(json.as_bytes())?;
+    anyhow::Ok(())
+}
+
+/// 解析 URI 中 `?` 之后的查询参数
+pub fn query_params(uri: &str) -> HashMap<String, String> {
+    uri.split_once('?')
+        .map(|(_, query)| parse_form(query))
+        .unwrap_or_default()
+}
+
+/// 读取完整的请求体，最多 1KB
+pub fn read_body(req: &mut Request<&mut EspHttpConnection>) -> anyhow::Result<String> {
+    read_body_limited(req, 1024)
+}
+
+/// 请求体超过了 read_body_limited 的上限
+#[derive(Debug)]
+pub struct BodyTooLarge(pub usize);
+
+impl std::fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "request body larger than {} bytes", self.0)
+    }
+}
+
+impl std::error::Error for BodyTooLarge {}
+
+/// 最多读取 limit 字节，超出时返回 BodyTooLarge，不会截断后继续处理
+pub fn read_body_limited(
+    req: &mut Request<&mut EspHttpConnection>,
+    limit: usize,
+) -> anyhow::Result<String> {
+    // 多读一个字节用来判断是否超出
+    let mut buffer = vec![0u8; limit + 1];
+    let mut len = 0;
+    while len < buffer.len() {
+        let n = req.read(&mut buffer[len..])?;
+        if n == 0 {
+            break;
+        }
+        len += n;
+    }
+    if len > limit {
+        return Err(BodyTooLarge(limit).into());
+    }
+    Ok(String::from_utf8_lossy(&buffer[..len]).into_owned())
 }
@@ -1,6 +1,11 @@
 
 mod app;
+mod config;
+mod console;
 mod display;
+mod dns;
+mod http_server;
+mod mdns;
 mod weather;
 mod wifi;
 mod button;
@@ -0,0 +1,38 @@
+use esp_idf_svc::mdns::EspMdns;
+use log::info;
+use std::sync::Mutex;
+
+pub const FIRMWARE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// 在局域网内广播 `<hostname>.local` 和 `_http._tcp` 服务，
+/// TXT 记录带上固件版本和设备 ID，方便桌面工具和 Home Assistant 发现设备
+pub fn start_mdns(hostname: &str, device_id: &str) -> anyhow::Result<EspMdns> {
+    let mut mdns = EspMdns::take()?;
+    mdns.set_hostname(hostname)?;
+    mdns.set_instance_name("ESP32 Weather")?;
+    mdns.add_service(
+        Some(hostname),
+        "_http",
+        "_tcp",
+        80,
+        &[
+            ("version", FIRMWARE_VERSION),
+            ("id", device_id),
+            ("path", "/api/status"),
+        ],
+    )?;
+    info!("mDNS started: {}.local", hostname);
+    Ok(mdns)
+}
+
+/// 主机名修改后按新名字重新广播。EspMdns 同一时间只能有一个实例，先释放旧的
+pub fn restart_mdns(
+    mdns: &Mutex<Option<EspMdns>>,
+    hostname: &str,
+    device_id: &str,
+) -> anyhow::Result<()> {
+    let mut mdns = mdns.lock().unwrap();
+    mdns.take();
+    *mdns = Some(start_mdns(hostname, device_id)?);
+    Ok(())
+}
@@ -1,37 +1,119 @@
-use crate::{app::AppPage, wifi::WifiStatus};
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    app::AppPage,
+    weather::{WeatherAlert, WeatherData, WeatherSnapshot},
+    wifi::{WifiInfo, WifiStatus},
+};
+
+/// 错过一次刷新不算过期：刷新间隔的两倍，且至少比刷新间隔多半小时
+fn weather_stale_after(refresh_interval: Duration) -> Duration {
+    (refresh_interval * 2).max(refresh_interval + Duration::from_secs(30 * 60))
+}
 
 #[derive(Clone)]
 pub struct State {
     pub page: AppPage,
-    pub weather: String,
+    pub weather: Option<WeatherData>,
+    /// 最近一次成功获取天气的时间
+    pub weather_updated: Option<Instant>,
+    /// 最近一次获取失败的原因，成功后清空
+    pub weather_error: Option<String>,
+    /// 从 NVS 缓存恢复的数据，本次启动还没有获取成功过
+    pub weather_cache: Option<WeatherSnapshot>,
+    /// 超过这个时间没有更新的天气数据视为过期，随刷新间隔变化
+    pub weather_stale_after: Duration,
+    pub alerts: Vec<WeatherAlert>,
+    /// 用户按键确认过的预警 id，不再占用屏幕
+    pub acknowledged_alerts: HashSet<String>,
     pub wifi_status: WifiStatus,
-    pub wifi_ip: String,
+    pub wifi_info: WifiInfo,
     pub date: String,
     pub time: String,
-    pub city: String,
 }
 impl State {
-    pub fn new() -> Self {
+    pub fn new(refresh_interval: Duration) -> Self {
         Self {
             page: AppPage::Home,
-            weather: String::from("--"),
+            weather: None,
+            weather_updated: None,
+            weather_error: None,
+            weather_cache: None,
+            weather_stale_after: weather_stale_after(refresh_interval),
+            alerts: Vec::new(),
+            acknowledged_alerts: HashSet::new(),
             wifi_status: WifiStatus::Disconnected,
-            wifi_ip: String::from("0.0.0.0"),
+            wifi_info: WifiInfo::default(),
             date: String::from("0000-00-00"),
             time: String::from("00:00:00"),
-            city: String::from(""),
         }
     }
     pub fn update_page(&mut self, page: AppPage) {
         self.page = page;
     }
-    pub fn update_wifi(&mut self, status: WifiStatus, ip: String) {
+    pub fn update_wifi(&mut self, status: WifiStatus, info: WifiInfo) {
         self.wifi_status = status;
-        self.wifi_ip = ip;
+        self.wifi_info = info;
+    }
+    pub fn update_weather(&mut self, weather: WeatherData) {
+        self.weather = Some(weather);
+        self.weather_updated = Some(Instant::now());
+        self.weather_error = None;
+        self.weather_cache = None;
+    }
+    /// 启动时恢复上一次缓存的天气
+    pub fn restore_weather(&mut self, snapshot: WeatherSnapshot) {
+        self.weather = Some(snapshot.weather.clone());
+        self.weather_cache = Some(snapshot);
+    }
+    /// 服务器返回 304，数据仍然是最新的
+    pub fn weather_unchanged(&mut self) {
+        self.weather_updated = Some(Instant::now());
+        self.weather_error = None;
+    }
+    pub fn set_refresh_interval(&mut self, refresh_interval: Duration) {
+        self.weather_stale_after = weather_stale_after(refresh_interval);
+    }
+    pub fn weather_failed(&mut self, error: String) {
+        self.weather_error = Some(error);
     }
-    pub fn update_weather(&mut self, weather: String, city: String) {
-        self.weather = weather;
-        self.city = city;
+    pub fn weather_age(&self) -> Option<Duration> {
+        match (self.weather_updated, &self.weather_cache) {
+            (Some(updated), _) => Some(updated.elapsed()),
+            (None, Some(snapshot)) => snapshot.age(),
+            (None, None) => None,
+        }
+    }
+    /// 最近一次获取失败，或者数据超过 weather_stale_after 没有更新；
+    /// 缓存的数据在时钟同步之前无法判断新旧，也视为过期
+    pub fn is_weather_stale(&self) -> bool {
+        if self.weather_error.is_some() {
+            return true;
+        }
+        match self.weather_age() {
+            Some(age) => age > self.weather_stale_after,
+            None => self.weather_cache.is_some(),
+        }
+    }
+    pub fn update_alerts(&mut self, alerts: Vec<WeatherAlert>) {
+        // 已经解除的预警不用再记着
+        self.acknowledged_alerts
+            .retain(|id| alerts.iter().any(|alert| &alert.id == id));
+        self.alerts = alerts;
+    }
+    /// 第一个还没有确认且没有过期的预警
+    pub fn pending_alert(&self) -> Option<&WeatherAlert> {
+        self.alerts
+            .iter()
+            .find(|alert| !alert.is_expired() && !self.acknowledged_alerts.contains(&alert.id))
+    }
+    pub fn acknowledge_alerts(&mut self) {
+        for alert in &self.alerts {
+            self.acknowledged_alerts.insert(alert.id.clone());
+        }
     }
     pub fn update_date_time(&mut self, date: String, time: String) {
         self.date = date;
@@ -41,7 +123,7 @@ impl State {
     pub fn get_current_page(&self) -> &AppPage {
         &self.page
     }
-    pub fn get_wifi_status(&self) -> (WifiStatus, String) {
-        (self.wifi_status.clone(), self.wifi_ip.clone())
+    pub fn get_wifi_status(&self) -> (WifiStatus, WifiInfo) {
+        (self.wifi_status.clone(), self.wifi_info.clone())
     }
 }
\ No newline at end of file
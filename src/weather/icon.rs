@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+/// 天气图标，顺序与 assets/weather.png 图集中的图标一致
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum WeatherIcon {
+    Sunny,
+    ClearNight,
+    Cloudy,
+    CloudyNight,
+    Overcast,
+    Rain,
+    Snow,
+    Fog,
+    Thunder,
+    #[default]
+    Unknown,
+}
+
+impl WeatherIcon {
+    /// 图集中的序号
+    pub fn index(self) -> u32 {
+        self as u32
+    }
+
+    /// sojson 的 type 字段，例如 "多云"、"雷阵雨"、"雨夹雪"、"扬沙"；
+    /// 关键字的顺序决定优先级，"雷阵雨" 显示雷电
+    pub fn from_sojson(kind: &str) -> Self {
+        const KEYWORDS: [(&str, WeatherIcon); 10] = [
+            ("雷", WeatherIcon::Thunder),
+            ("雪", WeatherIcon::Snow),
+            ("冰雹", WeatherIcon::Snow),
+            ("雨", WeatherIcon::Rain),
+            ("雾", WeatherIcon::Fog),
+            ("霾", WeatherIcon::Fog),
+            ("沙", WeatherIcon::Fog),
+            ("尘", WeatherIcon::Fog),
+            ("阴", WeatherIcon::Overcast),
+            ("云", WeatherIcon::Cloudy),
+        ];
+        if kind == "晴" {
+            return WeatherIcon::Sunny;
+        }
+        KEYWORDS
+            .iter()
+            .find(|(keyword, _)| kind.contains(keyword))
+            .map(|(_, icon)| *icon)
+            .unwrap_or_default()
+    }
+
+    /// WMO 天气代码 https://open-meteo.com/en/docs
+    pub fn from_wmo(code: u8) -> Self {
+        match code {
+            0 => WeatherIcon::Sunny,
+            1 | 2 => WeatherIcon::Cloudy,
+            3 => WeatherIcon::Overcast,
+            45 | 48 => WeatherIcon::Fog,
+            51..=67 | 80..=82 => WeatherIcon::Rain,
+            71..=77 | 85 | 86 => WeatherIcon::Snow,
+            95..=99 => WeatherIcon::Thunder,
+            _ => WeatherIcon::Unknown,
+        }
+    }
+
+    /// OpenWeatherMap 的 weather.id https://openweathermap.org/weather-conditions
+    pub fn from_openweathermap(id: u16) -> Self {
+        match id {
+            200..=299 => WeatherIcon::Thunder,
+            300..=599 => WeatherIcon::Rain,
+            600..=699 => WeatherIcon::Snow,
+            700..=799 => WeatherIcon::Fog,
+            800 => WeatherIcon::Sunny,
+            801 | 802 => WeatherIcon::Cloudy,
+            803 | 804 => WeatherIcon::Overcast,
+            _ => WeatherIcon::Unknown,
+        }
+    }
+
+    /// 晴和多云在夜间换成月亮图标
+    pub fn at_night(self) -> Self {
+        match self {
+            WeatherIcon::Sunny => WeatherIcon::ClearNight,
+            WeatherIcon::Cloudy => WeatherIcon::CloudyNight,
+            icon => icon,
+        }
+    }
+}
@@ -0,0 +1,625 @@
+mod icon;
+mod open_meteo;
+mod openweathermap;
+mod qweather;
+mod sojson;
+mod tls;
+
+use embedded_svc::http::{client::Client, Method};
+use esp_idf_svc::{
+    http::client::{Configuration, EspHttpConnection},
+    io::Read,
+};
+use miniz_oxide::inflate::TinflStatus;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+pub use icon::WeatherIcon;
+pub use open_meteo::OpenMeteo;
+pub use openweathermap::OpenWeatherMap;
+pub use qweather::QWeather;
+pub use sojson::Sojson;
+pub use tls::validate_pem;
+
+/// 各个天气源统一转换成的数据，温度单位为摄氏度
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WeatherData {
+    pub city: String,
+    pub temperature: f32,
+    pub description: String,
+    #[serde(default)]
+    pub icon: WeatherIcon,
+    pub humidity: Option<u8>,
+    pub air_quality: Option<AirQuality>,
+    /// 健康提示，例如 sojson 的 ganmao
+    pub advice: String,
+    /// 从今天开始的逐日预报
+    pub forecast: Vec<DailyForecast>,
+    /// 从当前整点开始的逐小时预报，只有部分天气源提供
+    #[serde(default)]
+    pub hourly: Vec<HourlyForecast>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AirQuality {
+    pub aqi: Option<u16>,
+    pub pm25: Option<f32>,
+    pub pm10: Option<f32>,
+    /// 优/良/轻度污染...
+    pub category: String,
+}
+impl AirQuality {
+    /// AQI 分级的上限，依次对应 优/良/轻度/中度/重度/严重污染
+    pub const LEVELS: [u16; 6] = [50, 100, 150, 200, 300, 500];
+    const CATEGORIES: [&'static str; 6] =
+        ["优", "良", "轻度污染", "中度污染", "重度污染", "严重污染"];
+
+    /// AQI 所在的级别，0 表示优
+    pub fn level(aqi: u16) -> usize {
+        Self::LEVELS
+            .iter()
+            .position(|max| aqi <= *max)
+            .unwrap_or(Self::LEVELS.len() - 1)
+    }
+    pub fn category_for(aqi: u16) -> &'static str {
+        Self::CATEGORIES[Self::level(aqi)]
+    }
+
+    /// 按 HJ 633-2012 由 PM2.5 和 PM10 浓度（μg/m³）计算 AQI，取两者分指数的较大值。
+    /// 只有 PM 浓度时用它代替其它标准（例如美国 EPA）的 AQI，分级才对得上
+    pub fn aqi_from_pm(pm25: Option<f32>, pm10: Option<f32>) -> Option<u16> {
+        const PM25_LIMITS: [f32; 8] = [0.0, 35.0, 75.0, 115.0, 150.0, 250.0, 350.0, 500.0];
+        const PM10_LIMITS: [f32; 8] = [0.0, 50.0, 150.0, 250.0, 350.0, 420.0, 500.0, 600.0];
+        let pm25 = pm25.map(|c| Self::iaqi(c, &PM25_LIMITS));
+        let pm10 = pm10.map(|c| Self::iaqi(c, &PM10_LIMITS));
+        pm25.max(pm10)
+    }
+    /// 空气质量分指数，在浓度限值之间线性插值并向上取整，超过最高限值按 500 计
+    fn iaqi(concentration: f32, limits: &[f32; 8]) -> u16 {
+        const IAQI: [f32; 8] = [0.0, 50.0, 100.0, 150.0, 200.0, 300.0, 400.0, 500.0];
+        let c = concentration.max(0.0);
+        let Some(i) = limits.windows(2).position(|range| c <= range[1]) else {
+            return 500;
+        };
+        let slope = (IAQI[i + 1] - IAQI[i]) / (limits[i + 1] - limits[i]);
+        (slope * (c - limits[i]) + IAQI[i]).ceil() as u16
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DailyForecast {
+    /// YYYY-MM-DD
+    pub date: String,
+    /// 星期三
+    pub weekday: String,
+    pub high: f32,
+    pub low: f32,
+    pub description: String,
+    #[serde(default)]
+    pub icon: WeatherIcon,
+    pub wind: String,
+    pub sunrise: String,
+    pub sunset: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct HourlyForecast {
+    /// HH:MM
+    pub time: String,
+    pub temperature: f32,
+    /// 降水概率，0-100
+    pub precipitation_probability: Option<u8>,
+}
+
+/// 气象预警
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WeatherAlert {
+    /// 用来记录用户是否已经确认过
+    pub id: String,
+    /// 例如 "暴雨蓝色预警"
+    pub event: String,
+    /// 发布单位
+    pub sender: String,
+    pub description: String,
+    /// 生效和结束的 Unix 时间戳（秒）
+    pub start: i64,
+    pub end: i64,
+}
+impl WeatherAlert {
+    /// 时钟还没有同步时视为仍然有效
+    pub fn is_expired(&self) -> bool {
+        unix_time().is_some_and(|now| now > self.end)
+    }
+}
+
+/// 缓存在 NVS 中的最近一次天气，重启后先显示它
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WeatherSnapshot {
+    pub weather: WeatherData,
+    /// 获取时的 Unix 时间戳（秒）
+    pub fetched_at: i64,
+}
+impl WeatherSnapshot {
+    /// NVS 字符串最长 4000 字节，只保留前几天的预报
+    const MAX_FORECAST_DAYS: usize = 7;
+
+    /// 时钟还没有同步时返回 None，此时的时间戳没有意义
+    pub fn new(weather: &WeatherData) -> Option<Self> {
+        let mut weather = weather.clone();
+        weather.forecast.truncate(Self::MAX_FORECAST_DAYS);
+        // 重启后逐小时数据很快就过时了，不值得占用空间
+        weather.hourly.clear();
+        Some(Self {
+            weather,
+            fetched_at: unix_time()?,
+        })
+    }
+    /// 距离获取时的时长，时钟还没有同步时返回 None
+    pub fn age(&self) -> Option<Duration> {
+        let elapsed = unix_time()? - self.fetched_at;
+        Some(Duration::from_secs(elapsed.max(0) as u64))
+    }
+}
+
+/// 当前的 Unix 时间，SNTP 同步之前返回 None
+pub fn unix_time() -> Option<i64> {
+    // 2024-01-01，早于这个时间说明时钟还没有同步
+    const MIN_VALID_TIME: i64 = 1_704_067_200;
+    let now = chrono::Utc::now().timestamp();
+    (now >= MIN_VALID_TIME).then_some(now)
+}
+
+/// 查询天气的位置，sojson 使用城市代码，其它天气源使用经纬度
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Location {
+    pub name: String,
+    #[serde(default)]
+    pub code: String,
+    pub latitude: f32,
+    pub longitude: f32,
+}
+impl Default for Location {
+    fn default() -> Self {
+        Self {
+            name: String::from("青岛"),
+            code: String::from("101120201"),
+            latitude: 36.07,
+            longitude: 120.38,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProviderKind {
+    #[default]
+    Sojson,
+    OpenMeteo,
+    OpenWeatherMap,
+}
+impl ProviderKind {
+    /// 各天气源允许的最短刷新间隔，避免触发限流
+    pub fn min_refresh_interval(&self) -> Duration {
+        match self {
+            // sojson 的数据大约半小时更新一次，请求过于频繁会被封禁
+            ProviderKind::Sojson => Duration::from_secs(30 * 60),
+            ProviderKind::OpenMeteo => Duration::from_secs(15 * 60),
+            // OpenWeatherMap 建议同一位置不要超过 10 分钟请求一次
+            ProviderKind::OpenWeatherMap => Duration::from_secs(10 * 60),
+        }
+    }
+    /// 固定证书在 "config" NVS 命名空间中的键（最长 15 个字符）
+    pub fn cert_key(&self) -> &'static str {
+        match self {
+            ProviderKind::Sojson => "cert_sojson",
+            ProviderKind::OpenMeteo => "cert_open_meteo",
+            ProviderKind::OpenWeatherMap => "cert_owm",
+        }
+    }
+}
+
+fn default_refresh_minutes() -> u32 {
+    60
+}
+
+/// 天气相关配置，保存在 NVS 中
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WeatherConfig {
+    #[serde(default)]
+    pub provider: ProviderKind,
+    /// OpenWeatherMap 需要 API key
+    #[serde(default)]
+    pub api_key: String,
+    #[serde(default)]
+    pub location: Location,
+    /// 刷新间隔（分钟），实际间隔不会小于天气源的最小值
+    #[serde(default = "default_refresh_minutes")]
+    pub refresh_minutes: u32,
+    /// 和风天气的 API key，设置后不论使用哪个天气源都从和风天气获取预警
+    #[serde(default)]
+    pub qweather_key: String,
+    /// 和风天气控制台中的 API Host，留空使用 devapi.qweather.com
+    #[serde(default)]
+    pub qweather_host: String,
+    /// 固定了 CA 证书的天气源（不是公钥固定，见 tls::set_pinned_cert），没有固定的
+    /// 使用 ESP-IDF 证书包。整个配置存为一个最长 4000 字节的 NVS 字符串，
+    /// 证书本身按 ProviderKind::cert_key 单独保存
+    #[serde(default)]
+    pub pinned: HashSet<ProviderKind>,
+}
+impl Default for WeatherConfig {
+    fn default() -> Self {
+        Self {
+            provider: ProviderKind::default(),
+            api_key: String::new(),
+            location: Location::default(),
+            refresh_minutes: default_refresh_minutes(),
+            qweather_key: String::new(),
+            qweather_host: String::new(),
+            pinned: HashSet::new(),
+        }
+    }
+}
+
+/// 城市搜索的候选结果，sojson 只有城市代码，其它天气源只有经纬度
+#[derive(Serialize, Clone, Debug)]
+pub struct LocationCandidate {
+    pub name: String,
+    /// 省份/国家等，用于区分同名城市
+    pub region: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f32>,
+}
+
+pub trait WeatherProvider: Send {
+    fn name(&self) -> &'static str;
+    /// 带上次的 validators 发送条件请求，数据没有变化时返回 None
+    fn fetch(
+        &self,
+        location: &Location,
+        validators: &mut Validators,
+    ) -> Result<Option<WeatherData>, WeatherError>;
+    /// 按名称搜索城市
+    fn search_location(&self, query: &str) -> Result<Vec<LocationCandidate>, WeatherError>;
+    /// 当前生效的气象预警，没有预警接口的天气源返回空列表
+    fn alerts(&self, _location: &Location) -> Result<Vec<WeatherAlert>, WeatherError> {
+        Ok(Vec::new())
+    }
+}
+
+pub struct WeatherClient {
+    config: WeatherConfig,
+    /// config.pinned 中各天气源的证书（PEM）
+    pinned_certs: HashMap<ProviderKind, String>,
+    provider: Box<dyn WeatherProvider>,
+    /// 配置了和风天气 key 时的预警来源
+    qweather: Option<QWeather>,
+    /// 上一次成功响应的 ETag / Last-Modified
+    validators: Validators,
+}
+
+impl WeatherClient {
+    pub fn new(config: WeatherConfig, pinned_certs: HashMap<ProviderKind, String>) -> Self {
+        let provider = provider_for(&config);
+        pin_cert_for(&config, &pinned_certs);
+        Self {
+            qweather: qweather_for(&config),
+            config,
+            pinned_certs,
+            provider,
+            validators: Validators::default(),
+        }
+    }
+    pub fn config(&self) -> &WeatherConfig {
+        &self.config
+    }
+    /// 切换天气源或位置，下一次刷新时生效
+    pub fn set_config(&mut self, config: WeatherConfig) {
+        self.provider = provider_for(&config);
+        self.qweather = qweather_for(&config);
+        pin_cert_for(&config, &self.pinned_certs);
+        self.config = config;
+        self.validators = Validators::default();
+    }
+    /// 固定或取消固定某个天气源的证书，调用方负责保存证书和配置
+    pub fn set_pinned_cert(&mut self, provider: ProviderKind, pem: Option<String>) {
+        match pem {
+            Some(pem) => {
+                self.pinned_certs.insert(provider, pem);
+                self.config.pinned.insert(provider);
+            }
+            None => {
+                self.pinned_certs.remove(&provider);
+                self.config.pinned.remove(&provider);
+            }
+        }
+        if provider == self.config.provider {
+            pin_cert_for(&self.config, &self.pinned_certs);
+        }
+    }
+    pub fn refresh_interval(&self) -> Duration {
+        let configured = Duration::from_secs(self.config.refresh_minutes as u64 * 60);
+        configured.max(self.config.provider.min_refresh_interval())
+    }
+    /// 返回 None 表示与上一次获取的数据相比没有变化
+    pub fn fetch_weather(&mut self) -> Result<Option<WeatherData>, WeatherError> {
+        log::info!("Fetching weather from {}", self.provider.name());
+        self.provider.fetch(&self.config.location, &mut self.validators)
+    }
+    pub fn search_location(&self, query: &str) -> Result<Vec<LocationCandidate>, WeatherError> {
+        self.provider.search_location(query)
+    }
+    /// 配置了和风天气时只使用它的预警，避免同一预警从两个来源各显示一次；
+    /// 否则使用天气源自己的预警接口
+    pub fn fetch_alerts(&self) -> Result<Vec<WeatherAlert>, WeatherError> {
+        match &self.qweather {
+            Some(qweather) => qweather.alerts(&self.config.location, self.config.provider),
+            None => self.provider.alerts(&self.config.location),
+        }
+    }
+}
+
+fn provider_for(config: &WeatherConfig) -> Box<dyn WeatherProvider> {
+    match config.provider {
+        ProviderKind::Sojson => Box::new(Sojson),
+        ProviderKind::OpenMeteo => Box::new(OpenMeteo),
+        ProviderKind::OpenWeatherMap => Box::new(OpenWeatherMap::new(config.api_key.clone())),
+    }
+}
+
+fn qweather_for(config: &WeatherConfig) -> Option<QWeather> {
+    if config.qweather_key.is_empty() {
+        return None;
+    }
+    Some(QWeather::new(
+        config.qweather_key.clone(),
+        config.qweather_host.clone(),
+    ))
+}
+
+/// 证书无效时退回证书包，请求时会报告 TLS 错误而不是静默放行
+fn pin_cert_for(config: &WeatherConfig, pinned_certs: &HashMap<ProviderKind, String>) {
+    let pem = pinned_certs
+        .get(&config.provider)
+        .filter(|_| config.pinned.contains(&config.provider))
+        .map(String::as_str);
+    if let Err(e) = tls::set_pinned_cert(pem) {
+        log::warn!("Pinned certificate for {:?} rejected: {}", config.provider, e);
+    }
+}
+
+/// 响应体的上限，sojson 带 15 天预报的完整文档大约 6KB
+const MAX_BODY_SIZE: usize = 16 * 1024;
+
+/// 请求天气接口时可能出现的错误
+#[derive(Debug)]
+pub enum WeatherError {
+    /// 连接、DNS 或读取失败
+    Network(String),
+    /// TLS 握手失败，包括证书校验不通过
+    Tls(String),
+    /// 非 2xx 响应
+    HttpStatus(u16),
+    /// 429/503 或天气源自己的限流提示，带有服务器要求的 Retry-After
+    RateLimited(Option<Duration>),
+    /// 期望 JSON 却拿到了别的内容，例如运营商的劫持页面
+    ContentType(String),
+    /// 响应体超过 MAX_BODY_SIZE
+    TooLarge,
+    /// 响应内容无法解析
+    Parse(String),
+    /// 缺少 API key 等配置
+    NotConfigured(&'static str),
+}
+
+impl std::fmt::Display for WeatherError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WeatherError::Network(e) => write!(f, "network error: {}", e),
+            WeatherError::Tls(e) => write!(f, "TLS error: {}", e),
+            WeatherError::HttpStatus(status) => write!(f, "HTTP status {}", status),
+            WeatherError::RateLimited(None) => write!(f, "rate limited"),
+            WeatherError::RateLimited(Some(retry_after)) => {
+                write!(f, "rate limited, retry after {}s", retry_after.as_secs())
+            }
+            WeatherError::ContentType(content_type) => {
+                write!(f, "unexpected content type: {}", content_type)
+            }
+            WeatherError::TooLarge => write!(f, "response larger than {} bytes", MAX_BODY_SIZE),
+            WeatherError::Parse(e) => write!(f, "parse error: {}", e),
+            WeatherError::NotConfigured(what) => write!(f, "not configured: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for WeatherError {}
+
+impl From<serde_json::Error> for WeatherError {
+    fn from(e: serde_json::Error) -> Self {
+        WeatherError::Parse(e.to_string())
+    }
+}
+
+fn network_error<E: std::fmt::Debug>(e: E) -> WeatherError {
+    WeatherError::Network(format!("{:?}", e))
+}
+
+/// 条件请求用的 ETag / Last-Modified，服务器认为没有变化时返回 304
+#[derive(Clone, Debug, Default)]
+pub struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// 请求 JSON 接口并反序列化
+fn get_json<T: DeserializeOwned>(url: &str) -> Result<T, WeatherError> {
+    let (content_type, body) = get(url)?;
+    parse_json(content_type, &body)
+}
+
+/// 条件请求 JSON 接口，304 时返回 None
+fn get_json_conditional<T: DeserializeOwned>(
+    url: &str,
+    validators: &mut Validators,
+) -> Result<Option<T>, WeatherError> {
+    match get_conditional(url, validators)? {
+        Some((content_type, body)) => Ok(Some(parse_json(content_type, &body)?)),
+        None => Ok(None),
+    }
+}
+
+fn parse_json<T: DeserializeOwned>(
+    content_type: Option<String>,
+    body: &[u8],
+) -> Result<T, WeatherError> {
+    if let Some(content_type) = content_type.filter(|c| !c.contains("json")) {
+        return Err(WeatherError::ContentType(content_type));
+    }
+    Ok(serde_json::from_slice(body)?)
+}
+
+fn get_text(url: &str) -> Result<String, WeatherError> {
+    let (_, body) = get(url)?;
+    String::from_utf8(body).map_err(|e| WeatherError::Parse(e.to_string()))
+}
+
+/// 发送 GET 请求，读取完整响应体直到 EOF，返回 Content-Type 和响应体
+fn get(url: &str) -> Result<(Option<String>, Vec<u8>), WeatherError> {
+    // 没有 validators 时服务器不会返回 304
+    get_conditional(url, &mut Validators::default())?.ok_or(WeatherError::HttpStatus(304))
+}
+
+/// 固定的证书只属于当前天气源，和风天气预警等其它服务始终使用证书包
+fn get_json_with_bundle<T: DeserializeOwned>(url: &str) -> Result<T, WeatherError> {
+    let (content_type, body) = request(url, &mut Validators::default(), false)?
+        .ok_or(WeatherError::HttpStatus(304))?;
+    parse_json(content_type, &body)
+}
+
+/// 带上 If-None-Match / If-Modified-Since 发送 GET 请求；
+/// 304 时返回 None，成功时用响应头更新 validators
+fn get_conditional(
+    url: &str,
+    validators: &mut Validators,
+) -> Result<Option<(Option<String>, Vec<u8>)>, WeatherError> {
+    request(url, validators, true)
+}
+
+/// pinned 为 false 时忽略固定的证书，使用证书包
+fn request(
+    url: &str,
+    validators: &mut Validators,
+    pinned: bool,
+) -> Result<Option<(Option<String>, Vec<u8>)>, WeatherError> {
+    let mut config = Configuration {
+        timeout: Some(Duration::from_secs(5)),
+        ..Default::default()
+    };
+    tls::configure(&mut config, pinned);
+    let mut client = Client::wrap(EspHttpConnection::new(&config).map_err(network_error)?);
+
+    let mut headers = Vec::new();
+    if let Some(etag) = &validators.etag {
+        headers.push(("If-None-Match", etag.as_str()));
+    }
+    if let Some(last_modified) = &validators.last_modified {
+        headers.push(("If-Modified-Since", last_modified.as_str()));
+    }
+    // TLS 握手在建立连接时进行
+    let request = match client.request(Method::Get, url, &headers) {
+        Ok(request) => request,
+        Err(e) => return Err(tls::connect_error(client.connection(), e)),
+    };
+    let mut response = request.submit().map_err(network_error)?;
+
+    let status = response.status();
+    if status == 304 {
+        log::info!("Weather not modified: {}", url);
+        return Ok(None);
+    }
+    if status == 429 || status == 503 {
+        // 只支持秒数形式的 Retry-After
+        let retry_after = response
+            .header("Retry-After")
+            .and_then(|value| value.trim().parse().ok())
+            .map(Duration::from_secs);
+        if status == 429 || retry_after.is_some() {
+            return Err(WeatherError::RateLimited(retry_after));
+        }
+    }
+    if !(200..300).contains(&status) {
+        return Err(WeatherError::HttpStatus(status));
+    }
+    let content_type = response.header("Content-Type").map(|c| c.to_ascii_lowercase());
+    // 和风天气不管请求头如何都返回 gzip
+    let gzip = response
+        .header("Content-Encoding")
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("gzip"));
+    let new_validators = Validators {
+        etag: response.header("ETag").map(str::to_string),
+        last_modified: response.header("Last-Modified").map(str::to_string),
+    };
+
+    let mut body = Vec::new();
+    let mut buffer = [0; 1024];
+    loop {
+        let bytes_read = response.read(&mut buffer).map_err(network_error)?;
+        if bytes_read == 0 {
+            break;
+        }
+        if body.len() + bytes_read > MAX_BODY_SIZE {
+            return Err(WeatherError::TooLarge);
+        }
+        body.extend_from_slice(&buffer[..bytes_read]);
+    }
+    if gzip {
+        body = gunzip(&body)?;
+    }
+    *validators = new_validators;
+    Ok(Some((content_type, body)))
+}
+
+/// 解压 gzip 响应体，解压后同样不能超过 MAX_BODY_SIZE
+fn gunzip(data: &[u8]) -> Result<Vec<u8>, WeatherError> {
+    let deflate =
+        gzip_payload(data).ok_or_else(|| WeatherError::Parse(String::from("bad gzip")))?;
+    let result = miniz_oxide::inflate::decompress_to_vec_with_limit(deflate, MAX_BODY_SIZE);
+    result.map_err(|e| match e.status {
+        TinflStatus::HasMoreOutput => WeatherError::TooLarge,
+        status => WeatherError::Parse(format!("gzip: {:?}", status)),
+    })
+}
+
+/// 跳过 gzip 头部（RFC 1952），返回其中的 deflate 数据
+fn gzip_payload(data: &[u8]) -> Option<&[u8]> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+    // 10 字节头部 + 8 字节的 CRC32 和原始长度
+    if data.len() < 18 || data[..3] != [0x1f, 0x8b, 0x08] {
+        return None;
+    }
+    let flags = data[3];
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        pos += 2 + u16::from_le_bytes([*data.get(pos)?, *data.get(pos + 1)?]) as usize;
+    }
+    // 文件名和注释以 \0 结尾
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            pos += data.get(pos..)?.iter().position(|b| *b == 0)? + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+    data.get(pos..data.len() - 8)
+}
@@ -0,0 +1,256 @@
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+
+use super::{
+    get_json, get_json_conditional, AirQuality, DailyForecast, HourlyForecast, Location, LocationCandidate,
+    Validators, WeatherData, WeatherError, WeatherIcon, WeatherProvider,
+};
+use crate::config::url_encode;
+
+const FORECAST_URL: &str = "https://api.open-meteo.com/v1/forecast";
+const GEOCODING_URL: &str = "https://geocoding-api.open-meteo.com/v1/search";
+const AIR_QUALITY_URL: &str = "https://air-quality-api.open-meteo.com/v1/air-quality";
+const FORECAST_DAYS: usize = 5;
+const FORECAST_HOURS: usize = 24;
+
+#[derive(Deserialize, Debug)]
+struct Forecast {
+    current: Current,
+    daily: Option<Daily>,
+    hourly: Option<Hourly>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Current {
+    temperature_2m: f32,
+    relative_humidity_2m: Option<f32>,
+    weather_code: u8,
+}
+
+/// 逐日数据，每个字段都是与 time 等长的数组
+#[derive(Deserialize, Debug)]
+struct Daily {
+    /// 2025-04-09
+    time: Vec<String>,
+    weather_code: Vec<u8>,
+    temperature_2m_max: Vec<f32>,
+    temperature_2m_min: Vec<f32>,
+    /// 2025-04-09T05:41
+    #[serde(default)]
+    sunrise: Vec<String>,
+    #[serde(default)]
+    sunset: Vec<String>,
+    /// km/h
+    #[serde(default)]
+    wind_speed_10m_max: Vec<f32>,
+}
+
+impl Daily {
+    fn days(&self) -> Vec<DailyForecast> {
+        // 只取 HH:MM
+        let time_of = |values: &[String], i: usize| {
+            values
+                .get(i)
+                .and_then(|v| v.split_once('T'))
+                .map(|(_, time)| time.to_string())
+                .unwrap_or_default()
+        };
+        self.time
+            .iter()
+            .enumerate()
+            .map(|(i, date)| {
+                let code = self.weather_code.get(i).copied().unwrap_or(u8::MAX);
+                DailyForecast {
+                    date: date.clone(),
+                    weekday: weekday_name(date),
+                    high: self.temperature_2m_max.get(i).copied().unwrap_or_default(),
+                    low: self.temperature_2m_min.get(i).copied().unwrap_or_default(),
+                    description: wmo_description(code).to_string(),
+                    icon: WeatherIcon::from_wmo(code),
+                    wind: self
+                        .wind_speed_10m_max
+                        .get(i)
+                        .map(|speed| format!("{:.0}km/h", speed))
+                        .unwrap_or_default(),
+                    sunrise: time_of(&self.sunrise, i),
+                    sunset: time_of(&self.sunset, i),
+                }
+            })
+            .collect()
+    }
+}
+
+/// 逐小时数据，每个字段都是与 time 等长的数组
+#[derive(Deserialize, Debug)]
+struct Hourly {
+    /// 2025-04-09T10:00
+    time: Vec<String>,
+    temperature_2m: Vec<f32>,
+    #[serde(default)]
+    precipitation_probability: Vec<Option<u8>>,
+}
+
+impl Hourly {
+    fn hours(&self) -> Vec<HourlyForecast> {
+        self.time
+            .iter()
+            .zip(&self.temperature_2m)
+            .enumerate()
+            .map(|(i, (time, temperature))| HourlyForecast {
+                time: time
+                    .split_once('T')
+                    .map(|(_, time)| time.to_string())
+                    .unwrap_or_default(),
+                temperature: *temperature,
+                precipitation_probability: self
+                    .precipitation_probability
+                    .get(i)
+                    .copied()
+                    .flatten(),
+            })
+            .collect()
+    }
+}
+
+/// 与 sojson 一致，例如 "星期三"
+fn weekday_name(date: &str) -> String {
+    const NAMES: [&str; 7] = ["一", "二", "三", "四", "五", "六", "日"];
+    date.parse::<NaiveDate>()
+        .map(|date| format!("星期{}", NAMES[date.weekday().num_days_from_monday() as usize]))
+        .unwrap_or_default()
+}
+
+#[derive(Deserialize, Debug)]
+struct AirQualityResponse {
+    current: CurrentAirQuality,
+}
+
+/// 接口的 us_aqi 按美国 EPA 标准分级，与国内标准不同，AQI 由 PM 浓度自己计算
+#[derive(Deserialize, Debug)]
+struct CurrentAirQuality {
+    pm2_5: Option<f32>,
+    pm10: Option<f32>,
+}
+
+impl From<CurrentAirQuality> for AirQuality {
+    fn from(current: CurrentAirQuality) -> Self {
+        let aqi = AirQuality::aqi_from_pm(current.pm2_5, current.pm10);
+        Self {
+            aqi,
+            pm25: current.pm2_5,
+            pm10: current.pm10,
+            category: aqi
+                .map(|aqi| AirQuality::category_for(aqi).to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Geocoding {
+    #[serde(default)]
+    results: Vec<GeocodingResult>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GeocodingResult {
+    name: String,
+    latitude: f32,
+    longitude: f32,
+    #[serde(default)]
+    country: String,
+    #[serde(default)]
+    admin1: String,
+}
+
+/// api.open-meteo.com，免费且不需要 API key
+pub struct OpenMeteo;
+
+impl OpenMeteo {
+    /// 空气质量是单独的接口，失败时不影响天气数据
+    fn air_quality(&self, location: &Location) -> Option<AirQuality> {
+        let url = format!(
+            "{}?latitude={}&longitude={}&current=pm2_5,pm10",
+            AIR_QUALITY_URL, location.latitude, location.longitude
+        );
+        match get_json::<AirQualityResponse>(&url) {
+            Ok(response) => Some(response.current.into()),
+            Err(e) => {
+                log::warn!("Open-Meteo air quality failed: {}", e);
+                None
+            }
+        }
+    }
+}
+
+impl WeatherProvider for OpenMeteo {
+    fn name(&self) -> &'static str {
+        "open-meteo"
+    }
+    fn fetch(
+        &self,
+        location: &Location,
+        validators: &mut Validators,
+    ) -> Result<Option<WeatherData>, WeatherError> {
+        let url = format!(
+            "{}?latitude={}&longitude={}&timezone=auto&forecast_days={}&forecast_hours={}\
+             &current=temperature_2m,relative_humidity_2m,weather_code\
+             &daily=weather_code,temperature_2m_max,temperature_2m_min,sunrise,sunset,wind_speed_10m_max\
+             &hourly=temperature_2m,precipitation_probability",
+            FORECAST_URL, location.latitude, location.longitude, FORECAST_DAYS, FORECAST_HOURS
+        );
+        let Some(forecast) = get_json_conditional::<Forecast>(&url, validators)? else {
+            return Ok(None);
+        };
+        Ok(Some(WeatherData {
+            city: location.name.clone(),
+            temperature: forecast.current.temperature_2m,
+            description: wmo_description(forecast.current.weather_code).to_string(),
+            icon: WeatherIcon::from_wmo(forecast.current.weather_code),
+            humidity: forecast.current.relative_humidity_2m.map(|h| h.round() as u8),
+            forecast: forecast.daily.map(|daily| daily.days()).unwrap_or_default(),
+            hourly: forecast.hourly.map(|hourly| hourly.hours()).unwrap_or_default(),
+            air_quality: self.air_quality(location),
+            ..Default::default()
+        }))
+    }
+    fn search_location(&self, query: &str) -> Result<Vec<LocationCandidate>, WeatherError> {
+        let url = format!(
+            "{}?name={}&count=5&language=zh&format=json",
+            GEOCODING_URL,
+            url_encode(query)
+        );
+        let geocoding: Geocoding = get_json(&url)?;
+        Ok(geocoding
+            .results
+            .into_iter()
+            .map(|result| LocationCandidate {
+                name: result.name,
+                region: [result.admin1, result.country]
+                    .into_iter()
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                code: None,
+                latitude: Some(result.latitude),
+                longitude: Some(result.longitude),
+            })
+            .collect())
+    }
+}
+
+/// WMO 天气代码 https://open-meteo.com/en/docs
+pub fn wmo_description(code: u8) -> &'static str {
+    match code {
+        0 => "晴",
+        1 | 2 => "多云",
+        3 => "阴",
+        45 | 48 => "雾",
+        51..=57 => "毛毛雨",
+        61 | 63 | 66 | 80 | 81 => "雨",
+        65 | 67 | 82 => "大雨",
+        71..=77 | 85 | 86 => "雪",
+        95..=99 => "雷阵雨",
+        _ => "未知",
+    }
+}
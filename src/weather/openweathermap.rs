@@ -0,0 +1,167 @@
+use serde::Deserialize;
+
+use std::collections::HashMap;
+
+use super::{
+    get_json, get_json_conditional, Location, LocationCandidate, Validators, WeatherAlert,
+    WeatherData, WeatherError, WeatherIcon, WeatherProvider,
+};
+use crate::config::url_encode;
+
+const WEATHER_URL: &str = "https://api.openweathermap.org/data/2.5/weather";
+const GEOCODING_URL: &str = "https://api.openweathermap.org/geo/1.0/direct";
+// 预警只在 One Call 3.0 中提供，需要单独订阅
+const ONE_CALL_URL: &str = "https://api.openweathermap.org/data/3.0/onecall";
+
+#[derive(Deserialize, Debug)]
+struct Current {
+    name: String,
+    main: Main,
+    weather: Vec<Condition>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Main {
+    temp: f32,
+    humidity: Option<u8>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Condition {
+    /// https://openweathermap.org/weather-conditions
+    id: u16,
+    description: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct OneCall {
+    #[serde(default)]
+    alerts: Vec<Alert>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Alert {
+    #[serde(default)]
+    sender_name: String,
+    event: String,
+    start: i64,
+    end: i64,
+    #[serde(default)]
+    description: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct GeocodingResult {
+    name: String,
+    #[serde(default)]
+    local_names: HashMap<String, String>,
+    lat: f32,
+    lon: f32,
+    #[serde(default)]
+    country: String,
+    #[serde(default)]
+    state: String,
+}
+
+/// api.openweathermap.org，需要在配置中填写 API key
+pub struct OpenWeatherMap {
+    api_key: String,
+}
+
+impl OpenWeatherMap {
+    pub fn new(api_key: String) -> Self {
+        Self { api_key }
+    }
+}
+
+impl WeatherProvider for OpenWeatherMap {
+    fn name(&self) -> &'static str {
+        "openweathermap"
+    }
+    fn fetch(
+        &self,
+        location: &Location,
+        validators: &mut Validators,
+    ) -> Result<Option<WeatherData>, WeatherError> {
+        if self.api_key.is_empty() {
+            return Err(WeatherError::NotConfigured("OpenWeatherMap API key"));
+        }
+        let url = format!(
+            "{}?lat={}&lon={}&appid={}&units=metric&lang=zh_cn",
+            WEATHER_URL, location.latitude, location.longitude, self.api_key
+        );
+        let Some(current) = get_json_conditional::<Current>(&url, validators)? else {
+            return Ok(None);
+        };
+        Ok(Some(WeatherData {
+            city: if location.name.is_empty() {
+                current.name
+            } else {
+                location.name.clone()
+            },
+            temperature: current.main.temp,
+            icon: current
+                .weather
+                .first()
+                .map(|c| WeatherIcon::from_openweathermap(c.id))
+                .unwrap_or_default(),
+            description: current
+                .weather
+                .into_iter()
+                .next()
+                .map(|c| c.description)
+                .unwrap_or_default(),
+            humidity: current.main.humidity,
+            ..Default::default()
+        }))
+    }
+    fn search_location(&self, query: &str) -> Result<Vec<LocationCandidate>, WeatherError> {
+        if self.api_key.is_empty() {
+            return Err(WeatherError::NotConfigured("OpenWeatherMap API key"));
+        }
+        let url = format!(
+            "{}?q={}&limit=5&appid={}",
+            GEOCODING_URL,
+            url_encode(query),
+            self.api_key
+        );
+        let results: Vec<GeocodingResult> = get_json(&url)?;
+        Ok(results
+            .into_iter()
+            .map(|mut result| LocationCandidate {
+                name: result.local_names.remove("zh").unwrap_or(result.name),
+                region: [result.state, result.country]
+                    .into_iter()
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                code: None,
+                latitude: Some(result.lat),
+                longitude: Some(result.lon),
+            })
+            .collect())
+    }
+    fn alerts(&self, location: &Location) -> Result<Vec<WeatherAlert>, WeatherError> {
+        if self.api_key.is_empty() {
+            return Err(WeatherError::NotConfigured("OpenWeatherMap API key"));
+        }
+        let url = format!(
+            "{}?lat={}&lon={}&appid={}&exclude=current,minutely,hourly,daily&lang=zh_cn",
+            ONE_CALL_URL, location.latitude, location.longitude, self.api_key
+        );
+        let one_call: OneCall = get_json(&url)?;
+        Ok(one_call
+            .alerts
+            .into_iter()
+            .map(|alert| WeatherAlert {
+                // 接口没有提供 id，同一预警的事件和开始时间不变
+                id: format!("{}-{}", alert.event, alert.start),
+                event: alert.event,
+                sender: alert.sender_name,
+                description: alert.description,
+                start: alert.start,
+                end: alert.end,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,123 @@
+use chrono::DateTime;
+use serde::Deserialize;
+
+use super::{get_json_with_bundle, Location, ProviderKind, WeatherAlert, WeatherError};
+
+const DEFAULT_HOST: &str = "devapi.qweather.com";
+
+#[derive(Deserialize, Debug)]
+struct WarningResponse {
+    /// 和风天气把错误放在 code 里，"200" 表示成功
+    code: String,
+    #[serde(default)]
+    warning: Vec<Warning>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Warning {
+    id: String,
+    #[serde(default)]
+    sender: String,
+    title: String,
+    #[serde(default)]
+    start_time: Option<String>,
+    #[serde(default)]
+    end_time: Option<String>,
+    /// active / update / cancel
+    #[serde(default)]
+    status: String,
+    /// Blue / Yellow / Orange / Red ...
+    #[serde(default)]
+    severity_color: String,
+    /// 例如 "暴雨"
+    #[serde(default)]
+    type_name: String,
+    #[serde(default)]
+    text: String,
+}
+
+impl Warning {
+    /// 标题是 "青岛市气象台发布暴雨蓝色预警信号" 这样的长句，屏幕上只显示 "暴雨蓝色预警"
+    fn event(&self) -> String {
+        let color = match self.severity_color.as_str() {
+            "White" => "白色",
+            "Blue" => "蓝色",
+            "Green" => "绿色",
+            "Yellow" => "黄色",
+            "Orange" => "橙色",
+            "Red" => "红色",
+            "Black" => "黑色",
+            _ => "",
+        };
+        if self.type_name.is_empty() {
+            return self.title.clone();
+        }
+        format!("{}{}预警", self.type_name, color)
+    }
+}
+
+/// 时间格式为 "2024-07-01T10:00+08:00"，没有秒
+fn parse_time(time: Option<&str>) -> Option<i64> {
+    let time = time?;
+    DateTime::parse_from_rfc3339(time)
+        .or_else(|_| DateTime::parse_from_str(time, "%Y-%m-%dT%H:%M%:z"))
+        .ok()
+        .map(|time| time.timestamp())
+}
+
+/// 和风天气的气象预警 https://dev.qweather.com/docs/api/warning/weather-warning/ ，
+/// 只用来获取预警，与天气源无关，需要单独申请 API key
+pub struct QWeather {
+    key: String,
+    host: String,
+}
+
+impl QWeather {
+    /// host 为空时使用公共的 devapi 域名，新注册的账号需要填控制台中的 API Host
+    pub fn new(key: String, host: String) -> Self {
+        let host = if host.is_empty() {
+            String::from(DEFAULT_HOST)
+        } else {
+            host
+        };
+        Self { key, host }
+    }
+
+    /// 预警要和天气来自同一个位置：sojson 按城市代码取天气，国内城市的 LocationID
+    /// 与它相同；其它天气源按经纬度，城市代码可能是之前留下的
+    pub fn alerts(
+        &self,
+        location: &Location,
+        provider: ProviderKind,
+    ) -> Result<Vec<WeatherAlert>, WeatherError> {
+        let location = match provider {
+            ProviderKind::Sojson if !location.code.is_empty() => location.code.clone(),
+            _ => format!("{:.2},{:.2}", location.longitude, location.latitude),
+        };
+        let url = format!(
+            "https://{}/v7/warning/now?location={}&key={}&lang=zh",
+            self.host, location, self.key
+        );
+        let response: WarningResponse = get_json_with_bundle(&url)?;
+        match response.code.as_str() {
+            "200" | "204" => {}
+            "402" | "429" => return Err(WeatherError::RateLimited(None)),
+            code => return Err(WeatherError::HttpStatus(code.parse().unwrap_or(0))),
+        }
+        Ok(response
+            .warning
+            .into_iter()
+            .filter(|warning| warning.status != "cancel")
+            .map(|warning| WeatherAlert {
+                event: warning.event(),
+                start: parse_time(warning.start_time.as_deref()).unwrap_or(0),
+                // 没有结束时间的预警一直显示，直到接口不再返回它
+                end: parse_time(warning.end_time.as_deref()).unwrap_or(i64::MAX),
+                id: warning.id,
+                sender: warning.sender,
+                description: warning.text,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,208 @@
+use serde::Deserialize;
+
+use super::{
+    get_json_conditional, get_text, AirQuality, DailyForecast, Location, LocationCandidate,
+    Validators, WeatherData, WeatherError, WeatherIcon, WeatherProvider,
+};
+use crate::config::url_encode;
+
+const WEATHER_URL: &str = "https://t.weather.sojson.com/api/weather/city/";
+// sojson 的城市代码与中国天气网一致，用它的搜索接口查代码（只有 http）
+const SEARCH_URL: &str = "http://toy1.weather.com.cn/search?cityname=";
+
+/// https://t.weather.sojson.com/api/weather/city/101120201 的完整响应
+#[derive(Deserialize, Debug)]
+pub struct Temperature {
+    #[serde(default)]
+    pub message: String,
+    pub status: u16,
+    /// 20250409
+    #[serde(default)]
+    pub date: String,
+    /// 2025-04-09 10:16:00
+    #[serde(default)]
+    pub time: String,
+    #[serde(rename = "cityInfo")]
+    pub city_info: CityInfo,
+    pub data: Data,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CityInfo {
+    pub city: String,
+    #[serde(default)]
+    pub citykey: String,
+    #[serde(default)]
+    pub parent: String,
+    #[serde(rename = "updateTime", default)]
+    pub update_time: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Data {
+    /// 湿度，例如 "86%"
+    #[serde(default)]
+    pub shidu: String,
+    pub pm25: Option<f32>,
+    pub pm10: Option<f32>,
+    /// 空气质量，例如 "优"
+    #[serde(default)]
+    pub quality: String,
+    /// 当前温度，例如 "11.9"
+    pub wendu: String,
+    /// 感冒指数提示
+    #[serde(default)]
+    pub ganmao: String,
+    #[serde(default)]
+    pub forecast: Vec<Forecast>,
+    pub yesterday: Option<Forecast>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Forecast {
+    /// 日，例如 "09"
+    #[serde(default)]
+    pub date: String,
+    /// 例如 "高温 14℃"
+    pub high: String,
+    /// 例如 "低温 9℃"
+    pub low: String,
+    /// 2025-04-09
+    #[serde(default)]
+    pub ymd: String,
+    /// 星期三
+    #[serde(default)]
+    pub week: String,
+    #[serde(default)]
+    pub sunrise: String,
+    #[serde(default)]
+    pub sunset: String,
+    pub aqi: Option<u16>,
+    /// 风向
+    #[serde(default)]
+    pub fx: String,
+    /// 风力
+    #[serde(default)]
+    pub fl: String,
+    /// 天气，例如 "多云"
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    #[serde(default)]
+    pub notice: String,
+}
+
+/// 从 "高温 14℃" 这样的文字中取出温度
+fn parse_temperature(text: &str) -> Option<f32> {
+    let number: String = text
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '-' || *c == '.')
+        .collect();
+    number.parse().ok()
+}
+
+impl From<Temperature> for WeatherData {
+    fn from(parsed: Temperature) -> Self {
+        let data = parsed.data;
+        let today = data.forecast.first();
+        // 预报里的 aqi 是全天的预测值，和实测的 quality 对不上；用实测的 PM 浓度计算
+        let aqi = AirQuality::aqi_from_pm(data.pm25, data.pm10);
+        Self {
+            city: parsed.city_info.city,
+            temperature: parse_temperature(&data.wendu).unwrap_or_default(),
+            description: today.map(|t| t.kind.clone()).unwrap_or_default(),
+            icon: today.map(|t| WeatherIcon::from_sojson(&t.kind)).unwrap_or_default(),
+            humidity: data.shidu.trim_end_matches('%').parse().ok(),
+            air_quality: Some(AirQuality {
+                aqi,
+                pm25: data.pm25,
+                pm10: data.pm10,
+                category: aqi
+                    .map(|aqi| AirQuality::category_for(aqi).to_string())
+                    .unwrap_or(data.quality),
+            }),
+            advice: data.ganmao,
+            forecast: data
+                .forecast
+                .iter()
+                .map(|day| DailyForecast {
+                    date: day.ymd.clone(),
+                    weekday: day.week.clone(),
+                    high: parse_temperature(&day.high).unwrap_or_default(),
+                    low: parse_temperature(&day.low).unwrap_or_default(),
+                    description: day.kind.clone(),
+                    icon: WeatherIcon::from_sojson(&day.kind),
+                    wind: format!("{}{}", day.fx, day.fl),
+                    sunrise: day.sunrise.clone(),
+                    sunset: day.sunset.clone(),
+                })
+                .collect(),
+            // sojson 没有逐小时预报
+            hourly: Vec::new(),
+        }
+    }
+}
+
+/// t.weather.sojson.com，只支持国内城市代码
+pub struct Sojson;
+
+impl WeatherProvider for Sojson {
+    fn name(&self) -> &'static str {
+        "sojson"
+    }
+    fn fetch(
+        &self,
+        location: &Location,
+        validators: &mut Validators,
+    ) -> Result<Option<WeatherData>, WeatherError> {
+        let url = format!("{}{}", WEATHER_URL, location.code);
+        let Some(parsed) = get_json_conditional::<Temperature>(&url, validators)? else {
+            return Ok(None);
+        };
+        // sojson 在 HTTP 200 的响应体里返回业务状态码，403 表示请求过于频繁
+        match parsed.status {
+            200 => {}
+            403 => return Err(WeatherError::RateLimited(None)),
+            status => {
+                log::warn!("sojson error {}: {}", status, parsed.message);
+                return Err(WeatherError::HttpStatus(status));
+            }
+        }
+        parse_temperature(&parsed.data.wendu).ok_or_else(|| {
+            WeatherError::Parse(format!("invalid temperature: {}", parsed.data.wendu))
+        })?;
+        Ok(Some(WeatherData::from(parsed)))
+    }
+    fn search_location(&self, query: &str) -> Result<Vec<LocationCandidate>, WeatherError> {
+        let url = format!("{}{}", SEARCH_URL, url_encode(query));
+        // 返回 JSONP: ([{"ref":"101120201~shandong~青岛~Qingdao~青岛~Qingdao~532~266000~SD~山东"}])
+        let text = get_text(&url)?;
+        let json = text
+            .trim()
+            .trim_start_matches('(')
+            .trim_end_matches(')');
+        let results: Vec<SearchResult> = serde_json::from_str(json)?;
+        Ok(results
+            .iter()
+            .filter_map(|result| {
+                let fields: Vec<&str> = result.r#ref.split('~').collect();
+                let code = *fields.first()?;
+                // 只保留 9 位数字的城市代码，过滤掉景点等
+                if code.len() != 9 || !code.chars().all(|c| c.is_ascii_digit()) {
+                    return None;
+                }
+                Some(LocationCandidate {
+                    name: fields.get(2)?.to_string(),
+                    region: fields.get(9).unwrap_or(&"").to_string(),
+                    code: Some(code.to_string()),
+                    latitude: None,
+                    longitude: None,
+                })
+            })
+            .collect())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchResult {
+    r#ref: String,
+}
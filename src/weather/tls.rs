@@ -0,0 +1,167 @@
+use esp_idf_svc::{
+    handle::RawHandle,
+    http::client::{Configuration, EspHttpConnection},
+    io::EspIOError,
+    sys::{
+        esp, esp_crt_bundle_attach, esp_http_client_get_and_clear_last_tls_error,
+        esp_tls_free_global_ca_store, esp_tls_set_global_ca_store,
+        ESP_ERR_ESP_TLS_CANNOT_CREATE_SOCKET, ESP_ERR_ESP_TLS_CANNOT_RESOLVE_HOSTNAME,
+        ESP_ERR_ESP_TLS_CONNECTION_TIMEOUT, ESP_ERR_ESP_TLS_FAILED_CONNECT_TO_HOST, ESP_OK,
+    },
+};
+use std::{
+    ffi::CString,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::{network_error, WeatherError};
+
+// esp-tls 只有一个全局 CA store，同一时间只会使用一个天气源
+static PINNED: AtomicBool = AtomicBool::new(false);
+
+const PEM_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const PEM_END: &str = "-----END CERTIFICATE-----";
+
+/// 保存前检查 PEM：每个 BEGIN 都有对应的 END，中间是合法的 base64，
+/// 解码后是一个完整的 DER SEQUENCE；可以包含多张证书
+pub fn validate_pem(pem: &str) -> Result<(), WeatherError> {
+    let invalid =
+        |reason: &str| WeatherError::Tls(format!("invalid pinned certificate: {}", reason));
+    let mut rest = pem.trim();
+    if rest.is_empty() {
+        return Err(invalid("empty"));
+    }
+    while !rest.is_empty() {
+        let body = rest
+            .strip_prefix(PEM_BEGIN)
+            .ok_or_else(|| invalid("expected BEGIN CERTIFICATE"))?;
+        let (body, after) = body
+            .split_once(PEM_END)
+            .ok_or_else(|| invalid("missing END CERTIFICATE"))?;
+        let der = base64_decode(body).ok_or_else(|| invalid("bad base64"))?;
+        if der_sequence_len(&der) != Some(der.len()) {
+            return Err(invalid("bad DER"));
+        }
+        rest = after.trim_start();
+    }
+    Ok(())
+}
+
+/// 标准 base64，忽略换行等空白
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut count = 0;
+    let mut padding = 0;
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => 0,
+            _ => return None,
+        };
+        // '=' 只能出现在末尾
+        if c == b'=' {
+            padding += 1;
+        } else if padding > 0 {
+            return None;
+        }
+        bits = bits << 6 | value as u32;
+        count += 1;
+        if count == 4 {
+            out.extend_from_slice(&[(bits >> 16) as u8, (bits >> 8) as u8, bits as u8]);
+            bits = 0;
+            count = 0;
+        }
+    }
+    if count != 0 || padding > 2 {
+        return None;
+    }
+    out.truncate(out.len() - padding);
+    Some(out)
+}
+
+/// DER 编码的 SEQUENCE 连同头部的总长度
+fn der_sequence_len(der: &[u8]) -> Option<usize> {
+    if *der.first()? != 0x30 {
+        return None;
+    }
+    let first = *der.get(1)? as usize;
+    if first < 0x80 {
+        return Some(2 + first);
+    }
+    let count = first & 0x7f;
+    if count == 0 || count > 4 {
+        return None;
+    }
+    let len = der
+        .get(2..2 + count)?
+        .iter()
+        .fold(0usize, |len, byte| len << 8 | *byte as usize);
+    Some(2 + count + len)
+}
+
+/// 把当前天气源的信任锚换成固定的 CA 证书（PEM），只有它签发的证书链能通过校验；
+/// None 时使用 ESP-IDF 自带的根证书包。
+///
+/// 这是 CA 固定而不是公钥固定：esp_http_client 不提供握手后的对端证书，无法核对服务器公钥，
+/// 固定的 CA 签发的任何证书都会被信任。要缩小范围就固定签发服务器证书的中间 CA；
+/// 自签名的服务器证书也可以直接固定，CA 签发的服务器证书本身不能作为信任锚
+pub fn set_pinned_cert(pem: Option<&str>) -> Result<(), WeatherError> {
+    unsafe { esp_tls_free_global_ca_store() };
+    PINNED.store(false, Ordering::Relaxed);
+    let Some(pem) = pem else {
+        return Ok(());
+    };
+    // mbedtls 解析 PEM 时要求以 \0 结尾，并且长度包含 \0
+    let pem = CString::new(pem.trim())
+        .map_err(|_| WeatherError::Tls(String::from("invalid pinned certificate")))?;
+    let pem = pem.as_bytes_with_nul();
+    esp!(unsafe { esp_tls_set_global_ca_store(pem.as_ptr(), pem.len() as u32) })
+        .map_err(|e| WeatherError::Tls(format!("invalid pinned certificate: {}", e)))?;
+    PINNED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// 固定了证书时只信任全局 CA store 中的证书，否则使用证书包；
+/// pinned 为 false 的请求不属于当前天气源，总是使用证书包
+pub fn configure(config: &mut Configuration, pinned: bool) {
+    if pinned && PINNED.load(Ordering::Relaxed) {
+        config.use_global_ca_store = true;
+        config.crt_bundle_attach = None;
+    } else {
+        config.use_global_ca_store = false;
+        config.crt_bundle_attach = Some(esp_crt_bundle_attach);
+    }
+}
+
+/// esp_http_client 把连接阶段的错误都报告成 ESP_ERR_HTTP_CONNECT，
+/// 这里查看 esp-tls 记录的最后一个错误，区分握手失败和网络不通
+pub fn connect_error(connection: &mut EspHttpConnection, e: EspIOError) -> WeatherError {
+    let mut tls_code = 0;
+    let mut tls_flags = 0;
+    let last_error = unsafe {
+        esp_http_client_get_and_clear_last_tls_error(
+            connection.handle(),
+            &mut tls_code,
+            &mut tls_flags,
+        )
+    };
+    let network_errors = [
+        ESP_ERR_ESP_TLS_CANNOT_RESOLVE_HOSTNAME,
+        ESP_ERR_ESP_TLS_CANNOT_CREATE_SOCKET,
+        ESP_ERR_ESP_TLS_FAILED_CONNECT_TO_HOST,
+        ESP_ERR_ESP_TLS_CONNECTION_TIMEOUT,
+    ];
+    if last_error == ESP_OK as i32 || network_errors.contains(&(last_error as u32)) {
+        return network_error(e);
+    }
+    // tls_flags 是 mbedtls 的证书校验结果，非 0 表示证书不受信任
+    WeatherError::Tls(format!(
+        "esp-tls error 0x{:x}, code -0x{:x}, verify flags 0x{:x}",
+        last_error, -tls_code, tls_flags
+    ))
+}
@@ -1,27 +1,61 @@
 use esp_idf_svc::{
     hal::modem::Modem,
-    eventloop::EspSystemEventLoop,
-    nvs::EspDefaultNvsPartition,
+    handle::RawHandle,
+    sys::{
+        esp, esp_eap_client_set_identity, esp_eap_client_set_password,
+        esp_eap_client_set_username, esp_netif_dns_info_t,
+        esp_netif_dns_type_t_ESP_NETIF_DNS_MAIN, esp_netif_set_dns_info, esp_wifi_set_mode,
+        esp_wifi_sta_enterprise_disable, esp_wifi_sta_enterprise_enable,
+        esp_wifi_sta_get_ap_info, wifi_ap_record_t, wifi_mode_t_WIFI_MODE_STA,
+        wifi_err_reason_t_WIFI_REASON_4WAY_HANDSHAKE_TIMEOUT,
+        wifi_err_reason_t_WIFI_REASON_AUTH_FAIL, wifi_err_reason_t_WIFI_REASON_HANDSHAKE_TIMEOUT,
+        wifi_err_reason_t_WIFI_REASON_NO_AP_FOUND, ESP_IPADDR_TYPE_V4,
+    },
+    eventloop::{EspSubscription, EspSystemEventLoop, System},
+    ipv4::{
+        self, ClientConfiguration as IpClientConfiguration, ClientSettings, DHCPClientSettings,
+        Mask, Subnet,
+    },
+    netif::{EspNetif, NetifConfiguration},
+    nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault},
     wifi::{
-        AccessPointConfiguration, AuthMethod, BlockingWifi, ClientConfiguration, Configuration, EspWifi
+        AccessPointConfiguration, AccessPointInfo, AuthMethod, BlockingWifi, ClientConfiguration, Configuration, EspWifi,
+        WifiEvent,
     },
 };
 use heapless::String as HString;
-use log::info;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::CStr,
+    net::Ipv4Addr,
+    sync::{Arc, Mutex},
+};
 
 
 // https://github.com/esp-rs/std-training/blob/main/common/lib/wifi/src/lib.rs
 
-const SSID: &str = "ChinaUnicom-UG9SCX";
-const PASSWORD: &str = "Buxingjie007";
+// keys in the "wifi" NVS namespace (max 15 chars)
+const NVS_KEY_NETWORKS: &str = "networks";
+const NVS_KEY_HOSTNAME: &str = "hostname";
+// single-network keys written by older firmware, migrated on load
+const NVS_KEY_SSID: &str = "ssid";
+const NVS_KEY_PASSWORD: &str = "password";
+
+const MAX_SAVED_NETWORKS: usize = 8;
+const HOSTNAME_PREFIX: &str = "esp32-weather";
 
 // static WIFI_CONFIG: Mutex<Option<(HString<32>, HString<32>)>> = Mutex::new(None);
 
-#[derive(PartialEq, Clone, Debug)]
+#[derive(Serialize, PartialEq, Clone, Debug)]
 pub enum WifiStatus {
     Disconnected,
     Connecting,
     Connected,
+    WrongPassword,
+    ApNotFound,
+    DhcpTimeout,
+    Provisioning,
 }
 impl ToString for WifiStatus {
     fn to_string(&self) -> String {
@@ -29,23 +63,345 @@ impl ToString for WifiStatus {
             WifiStatus::Connected => "Connected".to_string(),
             WifiStatus::Disconnected => "Disconnected".to_string(),
             WifiStatus::Connecting => "Connecting".to_string(),
+            WifiStatus::WrongPassword => "Wrong password".to_string(),
+            WifiStatus::ApNotFound => "AP not found".to_string(),
+            WifiStatus::DhcpTimeout => "DHCP timeout".to_string(),
+            WifiStatus::Provisioning => "Provisioning".to_string(),
+        }
+    }
+}
+impl WifiStatus {
+    /// 状态栏上显示的简短说明
+    pub fn label(&self) -> &'static str {
+        match self {
+            WifiStatus::Connected => "已连接",
+            WifiStatus::Disconnected => "未连接",
+            WifiStatus::Connecting => "连接中",
+            WifiStatus::WrongPassword => "密码错误",
+            WifiStatus::ApNotFound => "找不到网络",
+            WifiStatus::DhcpTimeout => "获取IP超时",
+            WifiStatus::Provisioning => "等待配网",
+        }
+    }
+}
+
+/// 当前连接的详细信息，未连接时各字段为空
+#[derive(Serialize, PartialEq, Clone, Debug, Default)]
+pub struct WifiInfo {
+    pub ssid: String,
+    pub ip: String,
+    pub rssi: i8,
+    pub channel: u8,
+    pub gateway: String,
+    pub dns: String,
+}
+impl WifiInfo {
+    /// 信号格数 0-4
+    pub fn signal_bars(&self) -> u8 {
+        match self.rssi {
+            rssi if rssi >= -55 => 4,
+            rssi if rssi >= -65 => 3,
+            rssi if rssi >= -75 => 2,
+            rssi if rssi >= -85 => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// 扫描到的 Wi-Fi 网络，供配网页面选择
+#[derive(Serialize, Clone, Debug)]
+pub struct WifiNetwork {
+    pub ssid: String,
+    pub rssi: i8,
+    pub channel: u8,
+    pub auth_method: String,
+    #[serde(skip)]
+    pub auth: Option<AuthMethod>,
+}
+
+/// Station credentials entered by the user
+#[derive(PartialEq, Clone, Debug)]
+pub struct WifiCredentials {
+    pub ssid: String,
+    pub password: String,
+    pub static_ip: Option<StaticIp>,
+    pub dns: Option<Ipv4Addr>,
+    pub enterprise: Option<EnterpriseAuth>,
+}
+
+/// WPA2-Enterprise (EAP-PEAP/TTLS) 账号，密码使用网络的 password 字段。
+/// 不校验 RADIUS 服务器证书
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct EnterpriseAuth {
+    /// 外层（匿名）身份
+    pub identity: String,
+    pub username: String,
+}
+
+/// 静态 IPv4 配置，不设置时使用 DHCP
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct StaticIp {
+    pub ip: Ipv4Addr,
+    pub gateway: Ipv4Addr,
+    /// 子网前缀长度，例如 24 表示 255.255.255.0
+    pub netmask: u8,
+}
+
+/// 保存在 "wifi" NVS 命名空间中的网络，priority 越大越优先
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct SavedNetwork {
+    pub ssid: String,
+    #[serde(skip_serializing_if = "String::is_empty", default)]
+    pub password: String,
+    pub priority: u8,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub static_ip: Option<StaticIp>,
+    /// 自定义 DNS 服务器，静态 IP 和 DHCP 下都生效
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub dns: Option<Ipv4Addr>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub enterprise: Option<EnterpriseAuth>,
+}
+
+impl From<&AccessPointInfo> for WifiNetwork {
+    fn from(ap: &AccessPointInfo) -> Self {
+        Self {
+            ssid: ap.ssid.to_string(),
+            rssi: ap.signal_strength,
+            channel: ap.channel,
+            auth_method: match ap.auth_method {
+                Some(auth_method) => format!("{:?}", auth_method),
+                None => String::from("Unknown"),
+            },
+            auth: ap.auth_method,
         }
     }
 }
+
 pub struct WiFiManager<'d> {
     wifi: BlockingWifi<EspWifi<'d>>,
+    sys_loop: EspSystemEventLoop,
+    nvs: EspNvs<NvsDefault>,
+    networks: Vec<SavedNetwork>,
+    current: Option<SavedNetwork>,
+    hostname: String,
+    // 当前 STA 网卡使用的 IP 配置，没有变化时不重建网卡
+    ip_config: Option<IpClientConfiguration>,
+    failure: Option<WifiStatus>,
+    disconnect_reason: Arc<Mutex<Option<u16>>>,
+    _disconnect_subscription: EspSubscription<'static, System>,
 }
 
 impl<'d> WiFiManager<'d> {
-    pub fn new(modem: Modem) -> anyhow::Result<Self>  {
-        let sys_loop = EspSystemEventLoop::take().unwrap();
-        let nvs = EspDefaultNvsPartition::take().unwrap();
-
+    pub fn new(
+        modem: Modem,
+        sys_loop: EspSystemEventLoop,
+        nvs_partition: EspDefaultNvsPartition,
+    ) -> anyhow::Result<Self>  {
+        let nvs = EspNvs::new(nvs_partition.clone(), "wifi", true)?;
         let wifi = BlockingWifi::wrap(
-            EspWifi::new(modem, sys_loop.clone(), Some(nvs)).unwrap(),
-            sys_loop
+            EspWifi::new(modem, sys_loop.clone(), Some(nvs_partition))?,
+            sys_loop.clone()
         )?;
-        Ok(Self { wifi })
+        // 记录最近一次断开的原因，用来区分密码错误和找不到网络
+        let disconnect_reason = Arc::new(Mutex::new(None));
+        let reason = disconnect_reason.clone();
+        let subscription = sys_loop.subscribe::<WifiEvent, _>(move |event| {
+            if let WifiEvent::StaDisconnected(disconnected) = event {
+                *reason.lock().unwrap() = Some(disconnected.reason());
+            }
+        })?;
+        let mut ins = Self {
+            wifi,
+            sys_loop,
+            nvs,
+            networks: Vec::new(),
+            current: None,
+            hostname: String::new(),
+            ip_config: None,
+            failure: None,
+            disconnect_reason,
+            _disconnect_subscription: subscription,
+        };
+        ins.networks = ins.load_networks()?;
+        let mut hostname_buf = [0u8; 33];
+        ins.hostname = match ins.nvs.get_str(NVS_KEY_HOSTNAME, &mut hostname_buf)? {
+            Some(hostname) => hostname.to_string(),
+            None => format!("{}-{}", HOSTNAME_PREFIX, ins.device_id()?),
+        };
+        Ok(ins)
+    }
+    pub const AP_SSID: &'static str = "ESP32_Weather_App";
+
+    /// SoftAP 的地址，配网页面和 DNS 劫持都指向它
+    pub fn ap_ip(&self) -> anyhow::Result<Ipv4Addr> {
+        Ok(self.wifi.wifi().ap_netif().get_ip_info()?.ip)
+    }
+    pub fn has_credentials(&self) -> bool {
+        !self.networks.is_empty()
+    }
+    /// 已保存的网络，按优先级从高到低
+    pub fn networks(&self) -> &[SavedNetwork] {
+        &self.networks
+    }
+    /// 当前连接的网络
+    pub fn current_network(&self) -> Option<&SavedNetwork> {
+        self.current.as_ref()
+    }
+    /// 从 NVS 读取保存的网络列表，兼容旧版本只存一组 ssid/password 的格式
+    fn load_networks(&mut self) -> anyhow::Result<Vec<SavedNetwork>> {
+        if let Some(len) = self.nvs.str_len(NVS_KEY_NETWORKS)? {
+            let mut buf = vec![0u8; len];
+            if let Some(json) = self.nvs.get_str(NVS_KEY_NETWORKS, &mut buf)? {
+                match serde_json::from_str::<Vec<SavedNetwork>>(json) {
+                    Ok(mut networks) => {
+                        networks.sort_by(|a, b| b.priority.cmp(&a.priority));
+                        return Ok(networks);
+                    }
+                    // 损坏的配置不能让设备反复重启，丢弃后回退到旧格式或重新配网
+                    Err(e) => {
+                        warn!("Saved Wi-Fi networks are corrupt, discarding: {:?}", e);
+                        self.nvs.remove(NVS_KEY_NETWORKS)?;
+                    }
+                }
+            }
+        }
+
+        let mut ssid_buf = [0u8; 33];
+        let mut password_buf = [0u8; 65];
+        let ssid = match self.nvs.get_str(NVS_KEY_SSID, &mut ssid_buf)? {
+            Some(ssid) if !ssid.is_empty() => ssid.to_string(),
+            _ => return Ok(Vec::new()),
+        };
+        let password = self
+            .nvs
+            .get_str(NVS_KEY_PASSWORD, &mut password_buf)?
+            .unwrap_or("")
+            .to_string();
+        info!("Migrating legacy Wi-Fi credentials for SSID: {}", ssid);
+        self.networks = vec![SavedNetwork {
+            ssid,
+            password,
+            priority: 0,
+            static_ip: None,
+            dns: None,
+            enterprise: None,
+        }];
+        self.store_networks()?;
+        self.nvs.remove(NVS_KEY_SSID)?;
+        self.nvs.remove(NVS_KEY_PASSWORD)?;
+        Ok(self.networks.clone())
+    }
+    fn store_networks(&mut self) -> anyhow::Result<()> {
+        let json = serde_json::to_string(&self.networks)?;
+        self.nvs.set_str(NVS_KEY_NETWORKS, &json)?;
+        Ok(())
+    }
+    /// 保存用户刚输入的网络，并设为最高优先级；内容未变化时不写入 flash
+    pub fn save_credentials(&mut self, credentials: WifiCredentials) -> anyhow::Result<()> {
+        let top = self.networks.first().map(|n| n.priority).unwrap_or(0);
+        if let Some(first) = self.networks.first() {
+            if first.ssid == credentials.ssid
+                && first.password == credentials.password
+                && first.static_ip == credentials.static_ip
+                && first.dns == credentials.dns
+                && first.enterprise == credentials.enterprise
+            {
+                return Ok(());
+            }
+        }
+        self.networks.retain(|n| n.ssid != credentials.ssid);
+        // 优先级用完时重新编号，保证新保存的网络仍然排在最前面
+        let top = if top == u8::MAX { self.renumber_networks() } else { top };
+        let priority = if self.networks.is_empty() { top } else { top + 1 };
+        self.networks.insert(
+            0,
+            SavedNetwork {
+                ssid: credentials.ssid,
+                password: credentials.password,
+                priority,
+                static_ip: credentials.static_ip,
+                dns: credentials.dns,
+                enterprise: credentials.enterprise,
+            },
+        );
+        self.networks.truncate(MAX_SAVED_NETWORKS);
+        self.store_networks()?;
+        info!("Wi-Fi credentials saved for SSID: {}", self.networks[0].ssid);
+        Ok(())
+    }
+    /// 添加或更新一个网络
+    pub fn add_network(&mut self, network: SavedNetwork) -> anyhow::Result<()> {
+        self.networks.retain(|n| n.ssid != network.ssid);
+        if self.networks.len() >= MAX_SAVED_NETWORKS {
+            anyhow::bail!("At most {} networks can be saved", MAX_SAVED_NETWORKS);
+        }
+        self.networks.push(network);
+        self.networks.sort_by(|a, b| b.priority.cmp(&a.priority));
+        self.store_networks()
+    }
+    pub fn remove_network(&mut self, ssid: &str) -> anyhow::Result<bool> {
+        let len = self.networks.len();
+        self.networks.retain(|n| n.ssid != ssid);
+        if self.networks.len() == len {
+            return Ok(false);
+        }
+        self.store_networks()?;
+        Ok(true)
+    }
+    /// 按给定顺序重新分配优先级，未列出的网络排在最后并保持原有顺序；
+    /// 重复的 SSID 只按第一次出现的位置排列
+    pub fn reorder_networks(&mut self, order: &[String]) -> anyhow::Result<()> {
+        let mut ordered: Vec<SavedNetwork> = Vec::with_capacity(self.networks.len());
+        for ssid in order.iter().chain(self.networks.iter().map(|n| &n.ssid)) {
+            if ordered.iter().any(|n| &n.ssid == ssid) {
+                continue;
+            }
+            if let Some(network) = self.networks.iter().find(|n| &n.ssid == ssid) {
+                ordered.push(network.clone());
+            }
+        }
+        self.networks = ordered;
+        self.renumber_networks();
+        self.store_networks()
+    }
+    /// 按当前顺序把优先级重新编为 n-1..0，返回最高的优先级
+    fn renumber_networks(&mut self) -> u8 {
+        let count = self.networks.len();
+        for (i, network) in self.networks.iter_mut().enumerate() {
+            network.priority = (count - 1 - i) as u8;
+        }
+        count.saturating_sub(1) as u8
+    }
+    /// 设备 ID：STA 网卡 MAC 地址的十六进制形式
+    pub fn device_id(&self) -> anyhow::Result<String> {
+        let mac = self.wifi.wifi().sta_netif().get_mac()?;
+        Ok(mac.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+    pub fn hostname(&self) -> &str {
+        &self.hostname
+    }
+    /// DHCP 主机名，下次连接时生效
+    pub fn set_hostname(&mut self, hostname: &str) -> anyhow::Result<()> {
+        let valid = !hostname.is_empty()
+            && hostname.len() <= 30
+            && hostname
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid {
+            anyhow::bail!("Invalid hostname: {}", hostname);
+        }
+        if self.hostname == hostname {
+            return Ok(());
+        }
+        self.nvs.set_str(NVS_KEY_HOSTNAME, hostname)?;
+        self.hostname = hostname.to_string();
+        Ok(())
+    }
+    pub fn clear_credentials(&mut self) -> anyhow::Result<()> {
+        self.nvs.remove(NVS_KEY_NETWORKS)?;
+        self.networks.clear();
+        Ok(())
     }
     // pub fn start_ap(&mut self) -> anyhow::Result<()> {
     //     let config = Configuration::AccessPoint(AccessPointConfiguration {
@@ -60,46 +416,313 @@ impl<'d> WiFiManager<'d> {
     //     Ok(())
     // }
     pub async fn connect(&mut self) -> anyhow::Result<()> {
-        self.set_config()?;
+        self.set_config(None)?;
 
         self.wifi.start()?;
         info!("Wifi started");
 
-        self.wifi.connect()?;
-        info!("Wifi connected");
-
-        self.wifi.wait_netif_up()?;
-        info!("Wifi netif up");
+        if self.networks.is_empty() {
+            info!("No Wi-Fi credentials stored, starting provisioning AP: {}", Self::AP_SSID);
+            return Ok(());
+        }
+        self.connect_best()
+    }
+    /// 使用新的配置重新连接；没有可用的网络时重新打开 SoftAP 供配网
+    pub fn reconnect(&mut self) -> anyhow::Result<()> {
+        let _ = self.wifi.disconnect();
+        self.current = None;
+        self.failure = None;
+        if self.networks.is_empty() {
+            return self.set_config(None);
+        }
+        self.connect_best()
+    }
+    /// 扫描后按优先级（相同优先级按信号强度）依次尝试在范围内的已保存网络，
+    /// 认证失败时继续尝试下一个
+    fn connect_best(&mut self) -> anyhow::Result<()> {
+        let in_range = self.scan()?;
+        let mut candidates: Vec<(SavedNetwork, i8, Option<AuthMethod>)> = self
+            .networks
+            .iter()
+            .filter_map(|saved| {
+                in_range
+                    .iter()
+                    .find(|n| n.ssid == saved.ssid)
+                    .map(|n| (saved.clone(), n.rssi, n.auth))
+            })
+            .collect();
+        candidates.sort_by(|(a, a_rssi, _), (b, b_rssi, _)| {
+            b.priority.cmp(&a.priority).then(b_rssi.cmp(a_rssi))
+        });
+        if candidates.is_empty() {
+            self.failure = Some(WifiStatus::ApNotFound);
+            // 之前的连接可能已经关闭了 SoftAP
+            self.set_config(None)?;
+            anyhow::bail!("No saved Wi-Fi network in range");
+        }
 
+        for (network, rssi, auth) in candidates {
+            let auth_method = Self::auth_method_for(&network, auth);
+            info!(
+                "Trying Wi-Fi {} ({} dBm, {:?})",
+                network.ssid, rssi, auth_method
+            );
+            // 配置无效（例如保存了过长的密码）只算这个网络失败，继续尝试下一个
+            let configured = self
+                .set_ip_config(&network)
+                .and_then(|()| self.set_enterprise(network.enterprise.as_ref(), &network.password))
+                .and_then(|()| self.set_config(Some((&network, auth_method))));
+            if let Err(e) = configured {
+                warn!("Wi-Fi {} configuration failed: {:?}", network.ssid, e);
+                self.failure = Some(WifiStatus::Disconnected);
+                continue;
+            }
+            *self.disconnect_reason.lock().unwrap() = None;
+            let failure = match self.wifi.connect() {
+                Ok(()) => match self.wifi.wait_netif_up() {
+                    Ok(()) => {
+                        self.apply_dns(&network);
+                        None
+                    }
+                    Err(e) => {
+                        warn!("Wi-Fi {} DHCP failed: {:?}", network.ssid, e);
+                        Some(WifiStatus::DhcpTimeout)
+                    }
+                },
+                Err(e) => {
+                    warn!("Wi-Fi {} failed: {:?}", network.ssid, e);
+                    Some(self.disconnect_failure())
+                }
+            };
+            match failure {
+                None => {
+                    info!("Wifi connected to {}", network.ssid);
+                    self.stop_ap()?;
+                    self.current = Some(network);
+                    self.failure = None;
+                    return Ok(());
+                }
+                Some(failure) => {
+                    self.failure = Some(failure);
+                    let _ = self.wifi.disconnect();
+                }
+            }
+        }
+        self.set_config(None)?;
+        anyhow::bail!(
+            "All saved Wi-Fi networks failed: {}",
+            self.failure.as_ref().map(|f| f.to_string()).unwrap_or_default()
+        )
+    }
+    /// 以扫描结果中的加密方式为准，扫描不到时根据保存的配置推断
+    fn auth_method_for(network: &SavedNetwork, scanned: Option<AuthMethod>) -> AuthMethod {
+        if network.enterprise.is_some() {
+            return AuthMethod::WPA2Enterprise;
+        }
+        match scanned {
+            Some(auth_method) => auth_method,
+            None if network.password.is_empty() => AuthMethod::None,
+            None => AuthMethod::WPA2Personal,
+        }
+    }
+    /// 开启或关闭 STA 的 EAP 认证
+    fn set_enterprise(
+        &mut self,
+        enterprise: Option<&EnterpriseAuth>,
+        password: &str,
+    ) -> anyhow::Result<()> {
+        let Some(enterprise) = enterprise else {
+            esp!(unsafe { esp_wifi_sta_enterprise_disable() })?;
+            return Ok(());
+        };
+        unsafe {
+            esp!(esp_eap_client_set_identity(
+                enterprise.identity.as_ptr(),
+                enterprise.identity.len() as i32
+            ))?;
+            esp!(esp_eap_client_set_username(
+                enterprise.username.as_ptr(),
+                enterprise.username.len() as i32
+            ))?;
+            esp!(esp_eap_client_set_password(
+                password.as_ptr(),
+                password.len() as i32
+            ))?;
+            esp!(esp_wifi_sta_enterprise_enable())?;
+        }
         Ok(())
     }
-    fn set_config(&mut self) -> anyhow::Result<()> {
-        let config = Configuration::Mixed(
-            ClientConfiguration {
-                ssid: HString::try_from(SSID).map_err(|_| anyhow::anyhow!("Invalid SSID"))?,
-                password: HString::try_from(PASSWORD).map_err(|_| anyhow::anyhow!("Invalid password"))?,
-                auth_method: AuthMethod::WPA2Personal,
+    /// 根据断开事件的原因码推断失败原因
+    fn disconnect_failure(&self) -> WifiStatus {
+        match self.disconnect_reason.lock().unwrap().map(u32::from) {
+            Some(
+                wifi_err_reason_t_WIFI_REASON_AUTH_FAIL
+                | wifi_err_reason_t_WIFI_REASON_4WAY_HANDSHAKE_TIMEOUT
+                | wifi_err_reason_t_WIFI_REASON_HANDSHAKE_TIMEOUT,
+            ) => WifiStatus::WrongPassword,
+            Some(wifi_err_reason_t_WIFI_REASON_NO_AP_FOUND) => WifiStatus::ApNotFound,
+            _ => WifiStatus::Disconnected,
+        }
+    }
+    /// 扫描附近的网络，同名网络只保留信号最强的一个，按信号强度排序
+    pub fn scan(&mut self) -> anyhow::Result<Vec<WifiNetwork>> {
+        let mut networks: Vec<WifiNetwork> = Vec::new();
+        for ap in self.wifi.scan()? {
+            if ap.ssid.is_empty() {
+                continue;
+            }
+            if let Some(existing) = networks.iter_mut().find(|n| n.ssid == ap.ssid.as_str()) {
+                if existing.rssi >= ap.signal_strength {
+                    continue;
+                }
+                *existing = WifiNetwork::from(&ap);
+            } else {
+                networks.push(WifiNetwork::from(&ap));
+            }
+        }
+        networks.sort_by(|a, b| b.rssi.cmp(&a.rssi));
+        Ok(networks)
+    }
+    /// 替换 STA 网卡：静态 IP 或带主机名的 DHCP；配置没有变化时保留原来的网卡
+    fn set_ip_config(&mut self, network: &SavedNetwork) -> anyhow::Result<()> {
+        let ip_config = match &network.static_ip {
+            Some(static_ip) => IpClientConfiguration::Fixed(ClientSettings {
+                ip: static_ip.ip,
+                subnet: Subnet {
+                    gateway: static_ip.gateway,
+                    mask: Mask(static_ip.netmask),
+                },
+                dns: network.dns,
+                secondary_dns: None,
+            }),
+            None => IpClientConfiguration::DHCP(DHCPClientSettings {
+                hostname: Some(
+                    HString::try_from(self.hostname.as_str())
+                        .map_err(|_| anyhow::anyhow!("Invalid hostname"))?,
+                ),
+            }),
+        };
+        if self.ip_config.as_ref() == Some(&ip_config) {
+            return Ok(());
+        }
+        let netif = EspNetif::new_with_conf(&NetifConfiguration {
+            ip_configuration: Some(ipv4::Configuration::Client(ip_config.clone())),
+            ..NetifConfiguration::wifi_default_client()
+        })?;
+        self.wifi.wifi_mut().swap_netif_sta(netif)?;
+        self.ip_config = Some(ip_config);
+        Ok(())
+    }
+    /// DHCP 模式下用自定义 DNS 覆盖租约中的 DNS；续租时会被改回，
+    /// 所以 get_wifi_status 发现不一致时会再次调用
+    fn apply_dns(&mut self, network: &SavedNetwork) {
+        let Some(dns) = network.dns.filter(|_| network.static_ip.is_none()) else {
+            return;
+        };
+        let mut dns_info: esp_netif_dns_info_t = unsafe { core::mem::zeroed() };
+        dns_info.ip.type_ = ESP_IPADDR_TYPE_V4 as u8;
+        // esp_ip4_addr_t 按网络字节序保存
+        dns_info.ip.u_addr.ip4.addr = u32::from_le_bytes(dns.octets());
+        let netif = self.wifi.wifi().sta_netif().handle();
+        let result = esp!(unsafe {
+            esp_netif_set_dns_info(netif, esp_netif_dns_type_t_ESP_NETIF_DNS_MAIN, &mut dns_info)
+        });
+        match result {
+            Ok(()) => info!("DNS server set to {}", dns),
+            Err(e) => warn!("Failed to set DNS server {}: {:?}", dns, e),
+        }
+    }
+    /// 连接成功后关闭配网热点，只保留 STA；不能用 set_configuration，
+    /// 重新设置 STA 配置会断开当前连接
+    fn stop_ap(&mut self) -> anyhow::Result<()> {
+        esp!(unsafe { esp_wifi_set_mode(wifi_mode_t_WIFI_MODE_STA) })?;
+        info!("Provisioning AP stopped");
+        Ok(())
+    }
+    /// 配网和连接过程中使用 Mixed 模式：SoftAP 用于配网，STA 用于扫描和连接；
+    /// 连接成功后由 stop_ap 关闭 SoftAP，没有网络可连时用 None 重新打开
+    fn set_config(&mut self, network: Option<(&SavedNetwork, AuthMethod)>) -> anyhow::Result<()> {
+        let ap_config = AccessPointConfiguration {
+            ssid: HString::try_from(Self::AP_SSID).unwrap(),
+            password: HString::try_from("").unwrap(),
+            channel: 1,
+            auth_method: AuthMethod::None,
+            max_connections: 4,
+            ..Default::default()
+        };
+        let client_config = match network {
+            // 企业网络的密码通过 EAP 设置，可能超过 64 字节
+            Some((network, AuthMethod::WPA2Enterprise)) => ClientConfiguration {
+                ssid: HString::try_from(network.ssid.as_str())
+                    .map_err(|_| anyhow::anyhow!("Invalid SSID"))?,
+                auth_method: AuthMethod::WPA2Enterprise,
                 ..Default::default()
             },
-            AccessPointConfiguration {
-                ssid: HString::try_from("ESP32_Weather_App").unwrap(),
-                password: HString::try_from("").unwrap(),
-                channel: 1,
-                auth_method: AuthMethod::None,
-                max_connections: 4,
+            Some((network, auth_method)) => ClientConfiguration {
+                ssid: HString::try_from(network.ssid.as_str())
+                    .map_err(|_| anyhow::anyhow!("Invalid SSID"))?,
+                password: HString::try_from(network.password.as_str())
+                    .map_err(|_| anyhow::anyhow!("Invalid password"))?,
+                auth_method,
                 ..Default::default()
             },
-        );
-        self.wifi.set_configuration(&config)?;
+            None => ClientConfiguration::default(),
+        };
+        self.wifi
+            .set_configuration(&Configuration::Mixed(client_config, ap_config))?;
         Ok(())
     }
-    pub async fn get_wifi_status(&mut self) -> anyhow::Result<(WifiStatus, String)>  {
-        if self.wifi.is_connected()? {
-            let ip = self.wifi.wifi().sta_netif().get_ip_info()?.ip;
-            return Ok((WifiStatus::Connected, ip.to_string()));
-        } else {
-            return Ok((WifiStatus::Connecting, String::from("0.0.0.0")));
+    /// STA 断开时回调，返回的订阅被 drop 后取消
+    pub fn on_disconnect<F: FnMut() + Send + 'static>(
+        &self,
+        mut callback: F,
+    ) -> anyhow::Result<EspSubscription<'static, System>> {
+        let subscription = self.sys_loop.subscribe::<WifiEvent, _>(move |event| {
+            if let WifiEvent::StaDisconnected(_) = event {
+                callback();
+            }
+        })?;
+        Ok(subscription)
+    }
+    pub async fn get_wifi_status(&mut self) -> anyhow::Result<(WifiStatus, WifiInfo)>  {
+        if !self.wifi.is_connected()? {
+            if self.networks.is_empty() {
+                return Ok((WifiStatus::Provisioning, WifiInfo::default()));
+            }
+            let status = self.failure.clone().unwrap_or(WifiStatus::Disconnected);
+            return Ok((status, WifiInfo::default()));
+        }
+        if !self.wifi.is_up()? {
+            return Ok((WifiStatus::Connecting, WifiInfo::default()));
+        }
+        let mut ip_info = self.wifi.wifi().sta_netif().get_ip_info()?;
+        if let Some(network) = self.current.clone() {
+            let dhcp_dns = network.static_ip.is_none() && network.dns.is_some();
+            if dhcp_dns && ip_info.dns != network.dns {
+                self.apply_dns(&network);
+                ip_info = self.wifi.wifi().sta_netif().get_ip_info()?;
+            }
         }
+        let mut info = WifiInfo {
+            ip: ip_info.ip.to_string(),
+            gateway: ip_info.subnet.gateway.to_string(),
+            dns: ip_info.dns.map(|dns| dns.to_string()).unwrap_or_default(),
+            ..Default::default()
+        };
+        if let Some(record) = Self::ap_record() {
+            info.ssid = CStr::from_bytes_until_nul(&record.ssid)
+                .map(|ssid| ssid.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            info.rssi = record.rssi;
+            info.channel = record.primary;
+        }
+        Ok((WifiStatus::Connected, info))
+    }
+    /// 当前连接的 AP 信息（信号强度、信道等）
+    fn ap_record() -> Option<wifi_ap_record_t> {
+        let mut record: wifi_ap_record_t = unsafe { core::mem::zeroed() };
+        esp!(unsafe { esp_wifi_sta_get_ap_info(&mut record) }).ok()?;
+        Some(record)
     }
     
 }